@@ -0,0 +1,459 @@
+use crate::document::{Block, Document, Provenance, Section};
+use crate::transformations::common::{markdown_table_row, markdown_table_rule};
+use serde::Serialize;
+
+/// How chunk sizes are measured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum ChunkUnit {
+    /// Unicode characters
+    Chars,
+    /// Approximate tokens (about 4 characters each)
+    #[default]
+    Tokens,
+}
+
+/// `--chunk-size` when not given.
+pub const DEFAULT_CHUNK_SIZE: usize = 512;
+/// `--chunk-overlap` when not given.
+pub const DEFAULT_CHUNK_OVERLAP: usize = 64;
+
+#[derive(Debug, Clone)]
+pub struct ChunkOptions {
+    pub max_size: usize,
+    pub overlap: usize,
+    pub unit: ChunkUnit,
+}
+
+impl Default for ChunkOptions {
+    fn default() -> Self {
+        ChunkOptions {
+            max_size: DEFAULT_CHUNK_SIZE,
+            overlap: DEFAULT_CHUNK_OVERLAP,
+            unit: ChunkUnit::default(),
+        }
+    }
+}
+
+/// One JSONL record of chunked output.
+#[derive(Debug, Serialize)]
+pub struct Chunk {
+    pub source: String,
+    pub chunk_index: usize,
+    /// Heading path from the outermost section down to the one containing the chunk.
+    pub headings: Vec<String>,
+    /// First and last page index (0-based) the chunk text came from.
+    pub page_start: u16,
+    pub page_end: u16,
+    /// Some of the text comes from an OCR layer kept with `--ocr-pages low-confidence`
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub low_confidence: bool,
+    pub text: String,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum UnitKind {
+    /// Never split (code blocks, tables)
+    Atomic,
+    /// Split at sentence, then word boundaries
+    Prose,
+    /// Split at line boundaries (lists, TOC)
+    Lines,
+}
+
+#[derive(Clone)]
+struct Unit {
+    text: String,
+    pages: (u16, u16),
+    kind: UnitKind,
+    low_confidence: bool,
+}
+
+/// Splits a document into chunks that never cross a heading boundary.
+pub fn chunk_document(document: &Document, source: &str, options: &ChunkOptions) -> Vec<Chunk> {
+    let mut groups: Vec<(Vec<String>, Vec<Unit>)> = Vec::new();
+    groups.push((Vec::new(), document.blocks.iter().map(block_unit).collect()));
+    collect_groups(&document.sections, &mut Vec::new(), &mut groups);
+
+    let mut chunks = Vec::new();
+    for (headings, units) in groups {
+        for packed in pack_units(&units, options) {
+            chunks.push(Chunk {
+                source: source.to_string(),
+                chunk_index: chunks.len(),
+                headings: headings.clone(),
+                page_start: packed.pages.0,
+                page_end: packed.pages.1,
+                low_confidence: packed.low_confidence,
+                text: packed.text,
+            });
+        }
+    }
+    chunks
+}
+
+fn collect_groups(
+    sections: &[Section],
+    breadcrumb: &mut Vec<String>,
+    groups: &mut Vec<(Vec<String>, Vec<Unit>)>,
+) {
+    for section in sections {
+        breadcrumb.push(section.heading.text.clone());
+        groups.push((
+            breadcrumb.clone(),
+            section.blocks.iter().map(block_unit).collect(),
+        ));
+        collect_groups(&section.sections, breadcrumb, groups);
+        breadcrumb.pop();
+    }
+}
+
+fn block_unit(block: &Block) -> Unit {
+    match block {
+        Block::Paragraph { text, provenance }
+        | Block::Footnote { text, provenance }
+        | Block::Caption {
+            text, provenance, ..
+        } => Unit {
+            text: text.clone(),
+            pages: page_range(provenance),
+            kind: UnitKind::Prose,
+            low_confidence: is_low_confidence(provenance),
+        },
+        Block::Code { text, provenance } => Unit {
+            text: text.clone(),
+            pages: page_range(provenance),
+            kind: UnitKind::Atomic,
+            low_confidence: is_low_confidence(provenance),
+        },
+        Block::List { items } => Unit {
+            text: items
+                .iter()
+                .map(|item| match item.number {
+                    Some(number) => format!("{}. {}", number, item.text),
+                    None => format!("- {}", item.text),
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+            pages: page_range(&items.iter().map(|i| i.provenance).collect::<Vec<_>>()),
+            kind: UnitKind::Lines,
+            low_confidence: items.iter().any(|i| i.provenance.low_confidence),
+        },
+        Block::Toc { entries, .. } => Unit {
+            text: entries
+                .iter()
+                .map(|entry| format!("{}{}", "  ".repeat(entry.level), entry.text))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            pages: page_range(&entries.iter().map(|e| e.provenance).collect::<Vec<_>>()),
+            kind: UnitKind::Lines,
+            low_confidence: entries.iter().any(|e| e.provenance.low_confidence),
+        },
+        Block::Table { rows, provenance } => Unit {
+            text: table_text(rows),
+            pages: page_range(provenance),
+            kind: UnitKind::Atomic,
+            low_confidence: is_low_confidence(provenance),
+        },
+        Block::ScannedPage { page, text } => Unit {
+            text: format!("[{}]", text),
+            pages: (*page, *page),
+            kind: UnitKind::Atomic,
+            low_confidence: false,
+        },
+    }
+}
+
+/// A table as Markdown, which embedding models read as a grid. The first row is the header.
+fn table_text(rows: &[Vec<String>]) -> String {
+    let mut lines: Vec<String> = rows.iter().map(|row| markdown_table_row(row)).collect();
+    let columns = rows.first().map_or(0, Vec::len);
+    lines.insert(1.min(lines.len()), markdown_table_rule(columns));
+    lines.join("\n")
+}
+
+fn is_low_confidence(provenance: &[Provenance]) -> bool {
+    provenance.iter().any(|p| p.low_confidence)
+}
+
+fn page_range(provenance: &[Provenance]) -> (u16, u16) {
+    let first = provenance.iter().map(|p| p.page).min().unwrap_or(0);
+    let last = provenance.iter().map(|p| p.page).max().unwrap_or(first);
+    (first, last)
+}
+
+/// Greedily packs units into chunks of at most `max_size`, splitting oversized prose and line
+/// units. Each new chunk starts with the last `overlap` worth of prose from the previous one.
+fn pack_units(units: &[Unit], options: &ChunkOptions) -> Vec<Unit> {
+    let max_size = options.max_size.max(1);
+    let overlap = options.overlap.min(max_size / 2);
+    let size = |text: &str| measure(text, options.unit);
+
+    let mut chunks = Vec::new();
+    let mut current: Vec<Unit> = Vec::new();
+    let mut current_size = 0;
+    // Whether `current` starts with overlap carried over from the previous chunk
+    let mut carried = false;
+
+    for unit in units {
+        if unit.text.trim().is_empty() {
+            continue;
+        }
+        for piece in split_unit(unit, max_size, options.unit) {
+            let piece_size = size(&piece.text);
+            if current.len() > usize::from(carried) && current_size + piece_size > max_size {
+                let tail = overlap_tail(&current, overlap, options.unit);
+                chunks.push(join_units(&current));
+                carried = tail.is_some();
+                current = tail.into_iter().collect();
+                current_size = current.iter().map(|u| size(&u.text)).sum();
+            }
+            current_size += piece_size;
+            current.push(piece);
+        }
+    }
+
+    if current.len() > usize::from(carried) {
+        chunks.push(join_units(&current));
+    }
+    chunks
+}
+
+/// Merges the units of a finished chunk into one, which is never split again.
+fn join_units(units: &[Unit]) -> Unit {
+    let text = units
+        .iter()
+        .map(|u| u.text.as_str())
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    let first = units.iter().map(|u| u.pages.0).min().unwrap_or(0);
+    let last = units.iter().map(|u| u.pages.1).max().unwrap_or(first);
+    Unit {
+        text,
+        pages: (first, last),
+        kind: UnitKind::Atomic,
+        low_confidence: units.iter().any(|u| u.low_confidence),
+    }
+}
+
+/// Trailing words of the last prose unit, used to start the next chunk. Code and lists are
+/// never used for overlap so that they are not cut mid-block.
+fn overlap_tail(units: &[Unit], overlap: usize, unit: ChunkUnit) -> Option<Unit> {
+    if overlap == 0 {
+        return None;
+    }
+    let last = units.last().filter(|u| u.kind == UnitKind::Prose)?;
+
+    let mut words: Vec<&str> = Vec::new();
+    let mut chars = 0;
+    for word in last.text.split_whitespace().rev() {
+        chars += word.chars().count() + usize::from(!words.is_empty());
+        words.push(word);
+        if scale(chars, unit) >= overlap {
+            break;
+        }
+    }
+    words.reverse();
+
+    Some(Unit {
+        text: words.join(" "),
+        pages: (last.pages.1, last.pages.1),
+        kind: UnitKind::Prose,
+        low_confidence: last.low_confidence,
+    })
+}
+
+fn split_unit(unit: &Unit, max_size: usize, size_unit: ChunkUnit) -> Vec<Unit> {
+    if unit.kind == UnitKind::Atomic || measure(&unit.text, size_unit) <= max_size {
+        return vec![unit.clone()];
+    }
+
+    let (parts, separator): (Vec<String>, &str) = match unit.kind {
+        UnitKind::Lines => (unit.text.lines().map(str::to_string).collect(), "\n"),
+        _ => (split_sentences(&unit.text, max_size, size_unit), " "),
+    };
+
+    let mut pieces = Vec::new();
+    let mut current = String::new();
+    let mut current_chars = 0;
+    for part in parts {
+        let part_chars = part.chars().count();
+        if !current.is_empty() && scale(current_chars + 1 + part_chars, size_unit) > max_size {
+            pieces.push(std::mem::take(&mut current));
+            current_chars = 0;
+        }
+        if !current.is_empty() {
+            current.push_str(separator);
+            current_chars += 1;
+        }
+        current.push_str(&part);
+        current_chars += part_chars;
+    }
+    if !current.is_empty() {
+        pieces.push(current);
+    }
+
+    pieces
+        .into_iter()
+        .map(|text| Unit {
+            text,
+            pages: unit.pages,
+            kind: unit.kind,
+            low_confidence: unit.low_confidence,
+        })
+        .collect()
+}
+
+/// Sentences of `text`; any sentence longer than `max_size` is further broken into word runs.
+fn split_sentences(text: &str, max_size: usize, unit: ChunkUnit) -> Vec<String> {
+    let mut sentences = Vec::new();
+    let mut current = String::new();
+    let mut current_chars = 0;
+    for word in text.split_whitespace() {
+        if !current.is_empty() {
+            current.push(' ');
+            current_chars += 1;
+        }
+        current.push_str(word);
+        current_chars += word.chars().count();
+        let sentence_end = word.ends_with(['.', '?', '!']);
+        if sentence_end || scale(current_chars, unit) >= max_size {
+            sentences.push(std::mem::take(&mut current));
+            current_chars = 0;
+        }
+    }
+    if !current.is_empty() {
+        sentences.push(current);
+    }
+    sentences
+}
+
+fn measure(text: &str, unit: ChunkUnit) -> usize {
+    scale(text.chars().count(), unit)
+}
+
+/// Size of `chars` characters of text in `unit`. Callers that grow text piece by piece keep a
+/// running character count and scale it, rather than measuring the whole text again.
+fn scale(chars: usize, unit: ChunkUnit) -> usize {
+    match unit {
+        ChunkUnit::Chars => chars,
+        ChunkUnit::Tokens => chars.div_ceil(4),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::Heading;
+    use crate::models::BoundingBox;
+
+    fn provenance(page: u16) -> Vec<Provenance> {
+        vec![Provenance {
+            page,
+            bbox: BoundingBox {
+                x: 0.0,
+                y: 0.0,
+                width: 0.0,
+                height: 0.0,
+            },
+            low_confidence: false,
+        }]
+    }
+
+    fn paragraph(text: &str, page: u16) -> Block {
+        Block::Paragraph {
+            text: text.to_string(),
+            provenance: provenance(page),
+        }
+    }
+
+    fn chars(max_size: usize, overlap: usize) -> ChunkOptions {
+        ChunkOptions {
+            max_size,
+            overlap,
+            unit: ChunkUnit::Chars,
+        }
+    }
+
+    fn texts(chunks: &[Chunk]) -> Vec<&str> {
+        chunks.iter().map(|chunk| chunk.text.as_str()).collect()
+    }
+
+    #[test]
+    fn splits_long_paragraphs_at_sentences_with_overlap() {
+        let document = Document {
+            page_count: 2,
+            blocks: vec![
+                paragraph("One two three. Four five six.", 0),
+                paragraph("Seven eight nine.", 1),
+            ],
+            sections: Vec::new(),
+        };
+        let chunks = chunk_document(&document, "a.pdf", &chars(20, 5));
+        assert_eq!(
+            texts(&chunks),
+            [
+                "One two three.",
+                "three.\n\nFour five six.",
+                "five six.\n\nSeven eight nine."
+            ]
+        );
+        assert_eq!((chunks[2].page_start, chunks[2].page_end), (0, 1));
+        assert!(chunks.iter().enumerate().all(|(i, c)| c.chunk_index == i));
+    }
+
+    #[test]
+    fn never_cuts_code_blocks_or_tables() {
+        let code = "fn main() {\n    println!(\"hello\");\n}";
+        let document = Document {
+            page_count: 1,
+            blocks: vec![
+                Block::Code {
+                    text: code.to_string(),
+                    provenance: provenance(0),
+                },
+                Block::Table {
+                    rows: vec![
+                        vec!["Name".to_string(), "Size".to_string()],
+                        vec!["Width".to_string(), "12".to_string()],
+                    ],
+                    provenance: provenance(0),
+                },
+            ],
+            sections: Vec::new(),
+        };
+        let chunks = chunk_document(&document, "a.pdf", &chars(10, 4));
+        assert_eq!(
+            texts(&chunks),
+            [code, "| Name | Size |\n| --- | --- |\n| Width | 12 |"]
+        );
+    }
+
+    #[test]
+    fn keeps_sections_apart() {
+        let document = Document {
+            page_count: 1,
+            blocks: vec![paragraph("Preface.", 0)],
+            sections: vec![Section {
+                heading: Heading {
+                    level: 1,
+                    text: "Setup".to_string(),
+                    provenance: provenance(0),
+                },
+                blocks: vec![paragraph("Install it.", 0)],
+                sections: vec![Section {
+                    heading: Heading {
+                        level: 2,
+                        text: "Linux".to_string(),
+                        provenance: provenance(0),
+                    },
+                    blocks: vec![paragraph("Use apt.", 0)],
+                    sections: Vec::new(),
+                }],
+            }],
+        };
+        let chunks = chunk_document(&document, "a.pdf", &ChunkOptions::default());
+        assert_eq!(texts(&chunks), ["Preface.", "Install it.", "Use apt."]);
+        assert_eq!(chunks[2].headings, ["Setup", "Linux"]);
+        assert!(chunks[0].headings.is_empty());
+    }
+}
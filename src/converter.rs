@@ -1,13 +1,37 @@
 use anyhow::Result;
 use pdfium_render::prelude::*;
 use rayon::prelude::*;
+use std::collections::BTreeMap;
 use std::path::Path;
 use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 
-use crate::models::{GlobalStats, ItemType, Page, ParseResult, TextItem};
+use crate::chunks::{chunk_document, ChunkOptions};
+use crate::document::Document;
+use crate::models::{
+    BoundingBox, Color, Figure, FontProperties, GlobalStats, ItemType, Page, ParseResult,
+    RenderMode, SourceSpan, TextItem,
+};
+use crate::ocr::{OcrProvider, OcrWord, PageImage};
 use crate::transformations::{
-    common::Transformation, compact_lines::CompactLines, detect_headers::DetectHeaders,
-    stats::CalculateGlobalStats, to_markdown::ToMarkdown,
+    common::{cjk_boundary, ends_sentence, is_cjk, Transformation},
+    compact_lines::{CompactLines, RotatedText},
+    detect_captions::DetectCaptions,
+    detect_headers::DetectHeaders,
+    detect_lists::DetectLists,
+    detect_ocr_pages::{DetectOcrPages, OcrPolicy},
+    detect_tables::DetectTables,
+    detect_vertical_writing::DetectVerticalWriting,
+    generate_toc::GenerateTOC,
+    link_toc::LinkTOC,
+    merge_headings::MergeHeadings,
+    normalize_headings::NormalizeHeadings,
+    normalize_text::NormalizeText,
+    remove_hidden_text::RemoveHiddenText,
+    stats::CalculateGlobalStats,
+    to_html::{html_document, ToHtml},
+    to_markdown::ToMarkdown,
+    to_text::ToText,
 };
 
 #[cfg(target_os = "windows")]
@@ -43,8 +67,142 @@ fn init_pdfium_library() -> Result<Box<dyn PdfiumLibraryBindings>> {
     )
 }
 
-/// Convert a PDF file at `path` to a Markdown string.
-pub fn convert_file(path: &Path, verbose: bool) -> Result<String> {
+/// Output representation produced for each input PDF.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Markdown document
+    #[default]
+    Markdown,
+    /// Hierarchical JSON document (sections, blocks and provenance)
+    Json,
+    /// Standalone HTML page
+    Html,
+    /// Plain text without markup, one paragraph per line (for search indexing)
+    Text,
+    /// JSONL chunks split at heading boundaries (for embedding pipelines)
+    Chunks,
+}
+
+impl OutputFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Markdown => "md",
+            OutputFormat::Json => "json",
+            OutputFormat::Html => "html",
+            OutputFormat::Text => "txt",
+            OutputFormat::Chunks => "jsonl",
+        }
+    }
+}
+
+/// How text is read from the page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum TextExtraction {
+    /// Whole text objects, with word spaces guessed from the gaps between them
+    #[default]
+    Objects,
+    /// Individual characters with pdfium's character boxes and generated spaces
+    Chars,
+}
+
+/// Which text from form XObjects (stamps, stamped headers, exported page content) to keep.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum XObjectText {
+    /// Keep all text inside form XObjects
+    #[default]
+    Include,
+    /// Drop form XObject text on pages that have text of their own, such as stamps
+    Exclude,
+}
+
+/// Settings that control how a single PDF is converted.
+#[derive(Debug, Clone, Default)]
+pub struct ConvertOptions {
+    pub verbose: bool,
+    pub format: OutputFormat,
+    /// Add `id="page-N"` anchors to HTML output
+    pub page_anchors: bool,
+    pub chunking: ChunkOptions,
+    /// Insert a TOC built from the detected headings, down to this many levels
+    pub generate_toc: Option<usize>,
+    /// Leading pages searched for an untitled TOC (DetectTOC's default if unset)
+    pub toc_scan_pages: Option<usize>,
+    /// Keep every top-level heading at H1 instead of only the first
+    pub multiple_h1: bool,
+    /// Levels added to every heading
+    pub heading_offset: usize,
+    /// Deepest heading level emitted (H6 if unset)
+    pub max_heading_level: Option<usize>,
+    /// What to do with the text of pages that carry an OCR layer
+    pub ocr_pages: OcrPolicy,
+    /// Whether text is read per text object or per character
+    pub extraction: TextExtraction,
+    /// Whether non-horizontal text is grouped along its own baseline or dropped
+    pub rotated_text: RotatedText,
+    /// Whether text inside form XObjects is kept on pages with text of their own
+    pub xobject_text: XObjectText,
+    /// Engine used to recognize scanned pages without a text layer (placeholders if unset)
+    pub ocr: Option<Arc<dyn OcrProvider>>,
+    /// Save image objects and reference them from the Markdown output
+    pub extract_images: bool,
+}
+
+/// Rendered output for a single PDF. The source map is only filled for Markdown.
+pub struct Conversion {
+    pub output: String,
+    pub source_map: Vec<SourceSpan>,
+    /// Images the Markdown references, to be written relative to it
+    pub images: Vec<Figure>,
+}
+
+/// Convert a PDF file at `path` into the requested output format.
+pub fn convert_file(path: &Path, options: &ConvertOptions) -> Result<Conversion> {
+    let mut result = parse_file(path, options)?;
+
+    match options.format {
+        OutputFormat::Markdown => Ok(render_markdown(&mut result, options.verbose)),
+        OutputFormat::Json => {
+            if options.verbose {
+                crate::lgger!("Building document tree...");
+            }
+            let document = Document::from_parse_result(&result);
+            Ok(Conversion {
+                output: serde_json::to_string_pretty(&document)?,
+                source_map: Vec::new(),
+                images: Vec::new(),
+            })
+        }
+        OutputFormat::Html => {
+            let title = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            Ok(render_html(&mut result, options, &title))
+        }
+        OutputFormat::Text => Ok(render_text(&mut result, options.verbose)),
+        OutputFormat::Chunks => {
+            let document = Document::from_parse_result(&result);
+            let chunks = chunk_document(&document, &path.display().to_string(), &options.chunking);
+            if options.verbose {
+                crate::lgger!("Split document into {} chunks", chunks.len());
+            }
+            let mut output = String::new();
+            for chunk in &chunks {
+                output.push_str(&serde_json::to_string(chunk)?);
+                output.push('\n');
+            }
+            Ok(Conversion {
+                output,
+                source_map: Vec::new(),
+                images: Vec::new(),
+            })
+        }
+    }
+}
+
+/// Extract a PDF and run the analysis pipeline up to (but not including) rendering.
+fn parse_file(path: &Path, options: &ConvertOptions) -> Result<ParseResult> {
+    let verbose = options.verbose;
     if verbose {
         crate::lgger!("Loading PDF from: {}", path.display());
     }
@@ -68,7 +226,7 @@ pub fn convert_file(path: &Path, verbose: bool) -> Result<String> {
 
     // 3. Extract Pages Parallelly
     let num_threads = rayon::current_num_threads();
-    let chunk_size = (total_pages as usize + num_threads - 1) / num_threads;
+    let chunk_size = (total_pages as usize).div_ceil(num_threads);
 
     // Create ranges
     let ranges: Vec<(u16, u16)> = (0..total_pages)
@@ -108,17 +266,71 @@ pub fn convert_file(path: &Path, verbose: bool) -> Result<String> {
 
             for page_idx in start..end {
                 if let Ok(page) = doc.pages().get(page_idx) {
-                    let items = extract_text_items(&page);
+                    let mut items = match options.extraction {
+                        TextExtraction::Objects => extract_text_items(&page, options.xobject_text),
+                        TextExtraction::Chars => {
+                            extract_text_items_by_char(&page, options.xobject_text)
+                        }
+                    };
+                    let frame = PageFrame::of(&page);
+                    for item in &mut items {
+                        if let ItemType::TextItem(text_item) = item {
+                            frame.place_text(text_item);
+                        }
+                    }
+                    let images: Vec<BoundingBox> = extract_image_regions(&page)
+                        .iter()
+                        .map(|bbox| frame.place(bbox))
+                        .collect();
+                    let scanned = items.is_empty() && is_scanned(&page, &images);
+                    let figures = if options.extract_images && !scanned {
+                        extract_figures(&page, &doc, page_idx + 1)
+                            .into_iter()
+                            .map(|figure| Figure {
+                                bbox: frame.place(&figure.bbox),
+                                ..figure
+                            })
+                            .collect()
+                    } else {
+                        Vec::new()
+                    };
+                    let mut ocr = false;
+                    if let (true, Some(provider)) = (scanned, &options.ocr) {
+                        match recognize_page(&page, provider.as_ref()) {
+                            Ok(recognized) => {
+                                ocr = !recognized.is_empty();
+                                items = recognized;
+                            }
+                            Err(e) => {
+                                crate::lgger!(
+                                    "Warning: {} failed on page {}: {:#}",
+                                    provider.name(),
+                                    page_idx + 1,
+                                    e
+                                );
+                            }
+                        }
+                    }
                     chunk_pages.push(Page {
                         index: page_idx,
                         items,
+                        images,
+                        figures,
+                        shaded: extract_shaded_regions(&page)
+                            .iter()
+                            .map(|bbox| frame.place(bbox))
+                            .collect(),
+                        scanned,
+                        ocr,
+                        low_confidence: false,
+                        unmapped_glyphs: 0,
                     });
                 }
 
                 // Progress log
                 if verbose {
                     let c = extraction_counter.fetch_add(1, Ordering::Relaxed) + 1;
-                    if c % 10 == 0 || c == total_pages as usize {
+                    if c.is_multiple_of(10) || c == total_pages as usize {
                         crate::lgger!("Extracted page {}/{}", c, total_pages);
                     }
                 }
@@ -131,6 +343,19 @@ pub fn convert_file(path: &Path, verbose: bool) -> Result<String> {
     // Sort pages by index
     pages.sort_by_key(|p| p.index);
 
+    let unrecognized = pages
+        .iter()
+        .filter(|page| page.scanned && !page.ocr)
+        .count();
+    if unrecognized > 0 && options.ocr.is_none() && options.ocr_pages != OcrPolicy::Skip {
+        crate::lgger!(
+            "Warning: {} has {} scanned page(s) without a text layer and no OCR engine was found; \
+             install tesseract to recognize them. These pages are replaced by placeholders.",
+            path.display(),
+            unrecognized
+        );
+    }
+
     if verbose {
         crate::lgger!(
             "Extracted {} pages in total. Calculating global stats...",
@@ -144,6 +369,37 @@ pub fn convert_file(path: &Path, verbose: bool) -> Result<String> {
         globals: GlobalStats::default(),
     };
 
+    if verbose {
+        crate::lgger!("Running NormalizeText...");
+    }
+    NormalizeText { verbose }.transform(&mut result);
+    for page in result.pages.iter().filter(|page| page.unmapped_glyphs > 0) {
+        crate::lgger!(
+            "Warning: extraction quality: {} page {}: {} glyph(s) without a Unicode mapping \
+             (font lacks ToUnicode) were dropped",
+            path.display(),
+            page.index + 1,
+            page.unmapped_glyphs
+        );
+    }
+
+    if verbose {
+        crate::lgger!("Running DetectOcrPages...");
+    }
+    DetectOcrPages {
+        verbose,
+        policy: options.ocr_pages,
+    }
+    .transform(&mut result);
+
+    // Drop text a reader never sees before it skews the stats
+    RemoveHiddenText { verbose }.transform(&mut result);
+
+    if verbose {
+        crate::lgger!("Running DetectVerticalWriting...");
+    }
+    DetectVerticalWriting { verbose }.transform(&mut result);
+
     // Calculate stats
     CalculateGlobalStats { verbose }.transform(&mut result);
 
@@ -167,7 +423,11 @@ pub fn convert_file(path: &Path, verbose: bool) -> Result<String> {
     if verbose {
         crate::lgger!("Running CompactLines...");
     }
-    CompactLines { verbose }.transform(&mut result);
+    CompactLines {
+        verbose,
+        rotated_text: options.rotated_text,
+    }
+    .transform(&mut result);
 
     if verbose {
         crate::lgger!("Running DetectCodeBlocks...");
@@ -179,17 +439,129 @@ pub fn convert_file(path: &Path, verbose: bool) -> Result<String> {
         crate::lgger!("Running DetectTOC...");
     }
     use crate::transformations::detect_toc::DetectTOC;
-    DetectTOC { verbose }.transform(&mut result);
+    DetectTOC {
+        verbose,
+        scan_pages: options.toc_scan_pages,
+    }
+    .transform(&mut result);
+
+    if verbose {
+        crate::lgger!("Running DetectTables...");
+    }
+    DetectTables { verbose }.transform(&mut result);
 
     if verbose {
         crate::lgger!("Running DetectHeaders...");
     }
     DetectHeaders { verbose }.transform(&mut result);
 
+    if verbose {
+        crate::lgger!("Running DetectCaptions...");
+    }
+    DetectCaptions { verbose }.transform(&mut result);
+
+    if verbose {
+        crate::lgger!("Running DetectLists...");
+    }
+    DetectLists { verbose }.transform(&mut result);
+
+    if verbose {
+        crate::lgger!("Running MergeHeadings...");
+    }
+    MergeHeadings { verbose }.transform(&mut result);
+
+    if verbose {
+        crate::lgger!("Running LinkTOC...");
+    }
+    LinkTOC { verbose }.transform(&mut result);
+
+    if verbose {
+        crate::lgger!("Running NormalizeHeadings...");
+    }
+    NormalizeHeadings {
+        verbose,
+        multiple_h1: options.multiple_h1,
+        heading_offset: options.heading_offset,
+        max_level: options.max_heading_level,
+    }
+    .transform(&mut result);
+
+    if let Some(max_depth) = options.generate_toc {
+        if verbose {
+            crate::lgger!("Running GenerateTOC...");
+        }
+        GenerateTOC { verbose, max_depth }.transform(&mut result);
+    }
+
+    Ok(result)
+}
+
+/// Render the analysed pages to a standalone HTML document.
+fn render_html(result: &mut ParseResult, options: &ConvertOptions, title: &str) -> Conversion {
+    if options.verbose {
+        crate::lgger!("Generating HTML...");
+    }
+    ToHtml {
+        verbose: options.verbose,
+        page_anchors: options.page_anchors,
+    }
+    .transform(result);
+
+    // Page fragments carry open elements across page breaks, so they are concatenated as-is
+    let body: String = result
+        .pages
+        .iter()
+        .flat_map(|p| p.items.iter())
+        .filter_map(|item| {
+            if let ItemType::Html(s) = item {
+                Some(s.as_str())
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    Conversion {
+        output: html_document(title, &body),
+        source_map: Vec::new(),
+        images: Vec::new(),
+    }
+}
+
+/// Render the analysed pages to plain text.
+fn render_text(result: &mut ParseResult, verbose: bool) -> Conversion {
+    if verbose {
+        crate::lgger!("Generating plain text...");
+    }
+    ToText { verbose }.transform(result);
+
+    // Page fragments already contain the separators needed to continue across pages
+    let text: String = result
+        .pages
+        .iter()
+        .flat_map(|p| p.items.iter())
+        .filter_map(|item| {
+            if let ItemType::PlainText(s) = item {
+                Some(s.as_str())
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    Conversion {
+        output: format!("{}\n", text.trim_end()),
+        source_map: Vec::new(),
+        images: Vec::new(),
+    }
+}
+
+/// Render the analysed pages to Markdown and join them into one document.
+fn render_markdown(result: &mut ParseResult, verbose: bool) -> Conversion {
     if verbose {
         crate::lgger!("Generating Markdown...");
     }
-    ToMarkdown { verbose }.transform(&mut result);
+    ToMarkdown { verbose }.transform(result);
 
     // Combine pages
     let page_markdowns: Vec<String> = result
@@ -207,7 +579,28 @@ pub fn convert_file(path: &Path, verbose: bool) -> Result<String> {
         })
         .collect();
 
+    let page_spans: Vec<Vec<SourceSpan>> = result
+        .pages
+        .iter()
+        .filter(|p| p.items.iter().any(|i| matches!(i, ItemType::Markdown(_))))
+        .map(|p| {
+            p.items
+                .iter()
+                .find_map(|item| {
+                    if let ItemType::SourceMap(spans) = item {
+                        Some(spans.clone())
+                    } else {
+                        None
+                    }
+                })
+                .unwrap_or_default()
+        })
+        .collect();
+
     let mut final_markdown = String::new();
+    let mut source_map = Vec::new();
+    // Newlines in `final_markdown`, counted as text is pushed rather than rescanned per page
+    let mut newlines = 0;
 
     for (i, page_md) in page_markdowns.iter().enumerate() {
         if i > 0 {
@@ -215,16 +608,15 @@ pub fn convert_file(path: &Path, verbose: bool) -> Result<String> {
             let trimmed_prev = prev.trim_end();
 
             if !trimmed_prev.is_empty() {
-                let last_char = trimmed_prev.chars().last().unwrap();
-                let is_sentence_end = ".?!\"”’".contains(last_char);
-
-                if is_sentence_end {
+                if ends_sentence(trimmed_prev) {
                     // Paragraph break needed. Ensure we have at least 2 newlines.
                     if !prev.ends_with("\n\n") {
                         if prev.ends_with('\n') {
                             final_markdown.push('\n');
+                            newlines += 1;
                         } else {
                             final_markdown.push_str("\n\n");
+                            newlines += 2;
                         }
                     }
                 } else {
@@ -233,48 +625,692 @@ pub fn convert_file(path: &Path, verbose: bool) -> Result<String> {
                     // If it's a paragraph split, prev likely ends with \n.
                     if !prev.ends_with('\n') {
                         final_markdown.push('\n');
+                        newlines += 1;
+                    } else if !prev.ends_with("\n\n") && cjk_boundary(trimmed_prev, page_md) {
+                        // CJK text continues without a line break, which would render as a space
+                        final_markdown.pop();
+                        newlines -= 1;
                     }
                     // If prev ends with \n\n, it remains a break.
                     // If prev ends with \n, it remains a soft wrap.
                 }
             } else {
                 final_markdown.push('\n');
+                newlines += 1;
             }
         }
+
+        // Page-relative lines become 1-based lines of the combined document
+        let line_offset = newlines + 1;
+        source_map.extend(page_spans[i].iter().cloned().map(|mut span| {
+            span.start_line += line_offset;
+            span.end_line += line_offset;
+            span
+        }));
+
         final_markdown.push_str(page_md);
+        newlines += page_md.matches('\n').count();
     }
 
-    Ok(final_markdown)
+    Conversion {
+        output: final_markdown,
+        source_map,
+        images: result
+            .pages
+            .iter_mut()
+            .flat_map(|page| std::mem::take(&mut page.figures))
+            .collect(),
+    }
 }
 
-fn extract_text_items(page: &PdfPage) -> Vec<ItemType> {
-    let mut items = Vec::new();
+/// Bounding boxes of the page's image objects, in the same coordinates as text items.
+fn extract_image_regions(page: &PdfPage) -> Vec<BoundingBox> {
+    page.objects()
+        .iter()
+        .filter(|object| object.as_image_object().is_some())
+        .filter_map(|object| image_bounds(&object))
+        .collect()
+}
+
+fn image_bounds(object: &PdfPageObject) -> Option<BoundingBox> {
+    let bounds = object.bounds().ok()?;
+    let bbox = BoundingBox {
+        x: bounds.left().value as f64,
+        y: bounds.top().value as f64,
+        width: (bounds.width().value).abs() as f64,
+        height: (bounds.height().value).abs() as f64,
+    };
+    (bbox.width > 0.0 && bbox.height > 0.0).then_some(bbox)
+}
 
+/// The page's image objects as JPEG files named after the page and their order on it, e.g.
+/// `assets/page-3-1.jpg`. Positions are as pdfium reports them, before the page frame.
+fn extract_figures(page: &PdfPage, doc: &PdfDocument, page_number: u16) -> Vec<Figure> {
+    let mut figures = Vec::new();
     for object in page.objects().iter() {
-        if let Some(text_object) = object.as_text_object() {
-            let text = text_object.text();
-            if text.trim().is_empty() {
-                continue;
+        let (Some(image), Some(bbox)) = (object.as_image_object(), image_bounds(&object)) else {
+            continue;
+        };
+        let encoded = image
+            .get_processed_image(doc)
+            .map_err(anyhow::Error::from)
+            .and_then(|image| encode_jpeg(&image));
+        match encoded {
+            Ok(data) => figures.push(Figure {
+                bbox,
+                path: format!("assets/page-{}-{}.jpg", page_number, figures.len() + 1),
+                data,
+            }),
+            Err(e) => {
+                crate::lgger!(
+                    "Warning: Failed to extract an image on page {}: {:#}",
+                    page_number,
+                    e
+                );
             }
+        }
+    }
+    figures
+}
+
+/// JPEG has no alpha channel, so transparent pixels are laid over white as on the page.
+fn encode_jpeg(image: &image::DynamicImage) -> Result<Vec<u8>> {
+    let rgba = image.to_rgba8();
+    let rgb = image::RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let [r, g, b, a] = rgba.get_pixel(x, y).0;
+        let over_white = |c: u8| ((c as u16 * a as u16 + 255 * (255 - a as u16)) / 255) as u8;
+        image::Rgb([over_white(r), over_white(g), over_white(b)])
+    });
+    let mut data = Vec::new();
+    image::codecs::jpeg::JpegEncoder::new_with_quality(&mut data, 90).encode_image(&rgb)?;
+    Ok(data)
+}
+
+/// Maps positions as pdfium reports them, before the page's `/Rotate` entry is applied, onto
+/// the page as displayed. Without it, text on a rotated page reads sideways.
+struct PageFrame {
+    /// Clockwise display rotation in degrees
+    rotation: f64,
+    /// Size of the page before rotation
+    width: f64,
+    height: f64,
+}
+
+impl PageFrame {
+    fn of(page: &PdfPage) -> Self {
+        let rotation = match page.rotation() {
+            Ok(PdfPageRenderRotation::Degrees90) => 90.0,
+            Ok(PdfPageRenderRotation::Degrees180) => 180.0,
+            Ok(PdfPageRenderRotation::Degrees270) => 270.0,
+            _ => 0.0,
+        };
+        // pdfium reports the size as displayed
+        let (width, height) = (page.width().value as f64, page.height().value as f64);
+        let (width, height) = if rotation == 90.0 || rotation == 270.0 {
+            (height, width)
+        } else {
+            (width, height)
+        };
+        PageFrame {
+            rotation,
+            width,
+            height,
+        }
+    }
+
+    fn place(&self, bbox: &BoundingBox) -> BoundingBox {
+        if self.rotation == 0.0 {
+            return *bbox;
+        }
+        // Turn clockwise about the origin like the page, then shift back onto the page
+        let turned = bbox.in_text_frame(self.rotation);
+        let (dx, dy) = match self.rotation as u32 {
+            90 => (0.0, self.width),
+            180 => (self.width, self.height),
+            _ => (self.height, 0.0),
+        };
+        BoundingBox {
+            x: turned.x + dx,
+            y: turned.y + dy,
+            ..turned
+        }
+    }
 
-            let font_name = text_object.font().name();
-            let bounds = text_object.bounds().unwrap_or(PdfQuadPoints::zero());
+    fn place_text(&self, item: &mut TextItem) {
+        let bbox = self.place(&BoundingBox {
+            x: item.x,
+            y: item.y,
+            width: item.width,
+            height: item.height,
+        });
+        item.x = bbox.x;
+        item.y = bbox.y;
+        item.width = bbox.width;
+        item.height = bbox.height;
+        item.angle = (item.angle - self.rotation).rem_euclid(360.0);
+    }
+}
 
-            let width = (bounds.width().value).abs() as f64;
-            let height = (bounds.height().value).abs() as f64;
+/// A page without text whose images cover at least half of it, as scanners produce.
+fn is_scanned(page: &PdfPage, images: &[BoundingBox]) -> bool {
+    let page_area = (page.width().value * page.height().value) as f64;
+    let image_area: f64 = images.iter().map(|bbox| bbox.width * bbox.height).sum();
+    page_area > 0.0 && image_area >= page_area * 0.5
+}
 
-            items.push(ItemType::TextItem(TextItem {
+/// Resolution scanned pages are rendered at for OCR.
+const OCR_DPI: f64 = 300.0;
+
+/// Renders the page and runs it through `provider`, giving one text item per recognized line.
+fn recognize_page(page: &PdfPage, provider: &dyn OcrProvider) -> Result<Vec<ItemType>> {
+    let config = PdfRenderConfig::new().scale_page_by_factor((OCR_DPI / 72.0) as f32);
+    let bitmap = page.render_with_config(&config)?;
+    let image = PageImage {
+        width: bitmap.width() as usize,
+        height: bitmap.height() as usize,
+        dpi: OCR_DPI,
+        rgba: bitmap.as_rgba_bytes(),
+    };
+    if image.width == 0 || image.height == 0 {
+        return Ok(Vec::new());
+    }
+    let words = provider.recognize(&image)?;
+
+    // Image pixels to PDF points, with y measured up from the bottom of the page
+    let scale = page.width().value as f64 / image.width as f64;
+    let page_height = page.height().value as f64;
+
+    let mut lines: BTreeMap<usize, Vec<&OcrWord>> = BTreeMap::new();
+    for word in &words {
+        lines.entry(word.line).or_default().push(word);
+    }
+
+    Ok(lines
+        .into_values()
+        .map(|mut words| {
+            words.sort_by(|a, b| a.left.total_cmp(&b.left));
+            let left = words.iter().map(|w| w.left).fold(f64::MAX, f64::min);
+            let top = words.iter().map(|w| w.top).fold(f64::MAX, f64::min);
+            let right = words.iter().map(|w| w.left + w.width).fold(0.0, f64::max);
+            let bottom = words.iter().map(|w| w.top + w.height).fold(0.0, f64::max);
+            let text = words
+                .iter()
+                .map(|w| w.text.as_str())
+                .collect::<Vec<_>>()
+                .join(" ");
+            // The line box spans ascenders to descenders, which is close to the font size
+            let height = (bottom - top) * scale;
+            ItemType::TextItem(TextItem {
                 text,
-                x: bounds.left().value as f64,
-                y: bounds.top().value as f64,
-                width,
+                x: left * scale,
+                y: page_height - top * scale,
+                width: (right - left) * scale,
                 height,
-                font: font_name,
-                font_size: text_object.scaled_font_size().value as f64,
+                font: "OCR".to_string(),
+                font_size: height,
                 format: None,
-            }));
+                font_properties: FontProperties::default(),
+                fill_color: None,
+                stroke_color: None,
+                render_mode: RenderMode::Fill,
+                highlighted: false,
+                angle: 0.0,
+                vertical: false,
+            })
+        })
+        .collect())
+}
+
+/// Bounding boxes of filled shapes in a visibly non-white color, such as callout boxes.
+fn extract_shaded_regions(page: &PdfPage) -> Vec<BoundingBox> {
+    page.objects()
+        .iter()
+        .filter(|object| {
+            object.as_path_object().is_some_and(|path| {
+                path.fill_mode()
+                    .is_ok_and(|mode| mode != PdfPathFillMode::None)
+            })
+        })
+        .filter(|object| {
+            object
+                .fill_color()
+                .is_ok_and(|fill| fill.alpha() > 0 && color(fill).lightness() < 0.9)
+        })
+        .filter_map(|object| object.bounds().ok())
+        .map(|bounds| BoundingBox {
+            x: bounds.left().value as f64,
+            y: bounds.top().value as f64,
+            width: (bounds.width().value).abs() as f64,
+            height: (bounds.height().value).abs() as f64,
+        })
+        .collect()
+}
+
+fn font_properties(font: &PdfFont) -> FontProperties {
+    let weight = font.weight().ok().and_then(|weight| match weight {
+        PdfFontWeight::Weight100 => Some(100),
+        PdfFontWeight::Weight200 => Some(200),
+        PdfFontWeight::Weight300 => Some(300),
+        PdfFontWeight::Weight400Normal => Some(400),
+        PdfFontWeight::Weight500 => Some(500),
+        PdfFontWeight::Weight600 => Some(600),
+        PdfFontWeight::Weight700Bold => Some(700),
+        PdfFontWeight::Weight800 => Some(800),
+        PdfFontWeight::Weight900 => Some(900),
+        PdfFontWeight::Custom(value) => (1..=1000).contains(&value).then_some(value),
+    });
+    FontProperties {
+        weight,
+        italic_angle: font.italic_angle().unwrap_or(0),
+        italic: font.is_italic(),
+        force_bold: font.is_bold_reenforced(),
+        fixed_pitch: font.is_fixed_pitch(),
+    }
+}
+
+fn color(color: PdfColor) -> Color {
+    Color {
+        r: color.red(),
+        g: color.green(),
+        b: color.blue(),
+        a: color.alpha(),
+    }
+}
+
+fn render_mode(mode: PdfPageTextRenderMode) -> RenderMode {
+    match mode {
+        PdfPageTextRenderMode::StrokedUnfilled | PdfPageTextRenderMode::StrokedUnfilledClipping => {
+            RenderMode::Stroke
+        }
+        PdfPageTextRenderMode::FilledThenStroked
+        | PdfPageTextRenderMode::FilledThenStrokedClipping => RenderMode::FillStroke,
+        PdfPageTextRenderMode::Invisible | PdfPageTextRenderMode::InvisibleClipping => {
+            RenderMode::Invisible
         }
+        _ => RenderMode::Fill,
     }
+}
+
+/// Applies `xobject_text` to items tagged with whether they came from a form XObject.
+fn keep_form_text(found: Vec<(TextItem, bool)>, xobject_text: XObjectText) -> Vec<ItemType> {
+    // Stamps are drawn as forms over a page with text of its own; some exporters put all of a
+    // page's text into forms, which is kept either way
+    let has_page_text = found.iter().any(|(_, in_form)| !in_form);
+    let keep_forms = match xobject_text {
+        XObjectText::Include => true,
+        XObjectText::Exclude => !has_page_text,
+    };
+    found
+        .into_iter()
+        .filter(|(_, in_form)| keep_forms || !in_form)
+        .map(|(item, _)| ItemType::TextItem(item))
+        .collect()
+}
+
+/// Builds text items from pdfium's per-character text page instead of whole text objects.
+/// Word breaks come from the space characters pdfium reports, including the ones it
+/// generates for kerning gaps and for glyphs placed one by one; a gap between glyph boxes
+/// only adds a space where pdfium reported none. An item is a run of characters on one line
+/// in one style, so `CompactLines` sees the spacing in the text rather than guessing it.
+fn extract_text_items_by_char(page: &PdfPage, xobject_text: XObjectText) -> Vec<ItemType> {
+    let Ok(text_page) = page.text() else {
+        return extract_text_items(page, xobject_text);
+    };
+    let highlights = highlight_regions(page);
+    let forms = form_regions(page);
+
+    let mut found: Vec<(TextItem, bool)> = Vec::new();
+    let mut run: Option<CharRun> = None;
+    let mut style: Option<ObjectStyle> = None;
+
+    for ch in text_page.chars().iter() {
+        let Some(c) = ch.unicode_char() else {
+            continue;
+        };
+        if c == '\r' || c == '\n' {
+            found.extend(run.take().map(CharRun::finish_line));
+            continue;
+        }
+        // pdfium generates a space wherever the gap between glyphs reads as a word break
+        let generated = ch.is_generated().unwrap_or(false);
+        if c.is_whitespace() {
+            if let Some(run) = &mut run {
+                if !run.item.text.ends_with(' ') {
+                    run.item.text.push(' ');
+                    run.generated_space = generated;
+                }
+            }
+            continue;
+        }
+        if generated {
+            continue;
+        }
+        let (Ok(bounds), Ok(text_object)) = (ch.loose_bounds(), ch.text_object()) else {
+            continue;
+        };
+        let center_x = bounds.left() + bounds.width() / 2.0;
+        let center_y = bounds.bottom() + bounds.height() / 2.0;
+
+        // Glyphs of one text object come one after another; its font, colors and matrix are
+        // read once for all of them
+        let object_bounds = text_object.bounds().ok();
+        let style = match &mut style {
+            Some(style) if object_bounds.is_some() && style.bounds == object_bounds => style,
+            _ => style.insert(ObjectStyle::new(
+                &text_object,
+                object_bounds,
+                &forms,
+                (center_x, center_y),
+            )),
+        };
+
+        let font_size = ch.scaled_font_size().value as f64;
+        let angle = style.angle;
+        let highlighted = highlights
+            .iter()
+            .any(|rect| rect.contains(center_x, center_y));
+
+        // Measured along the text's own baseline, so rotated runs read like horizontal ones
+        let glyph = BoundingBox {
+            x: bounds.left().value as f64,
+            y: bounds.top().value as f64,
+            width: bounds.width().value as f64,
+            height: bounds.height().value as f64,
+        }
+        .in_text_frame(angle);
+
+        if let Some(current) = &mut run {
+            let gap = glyph.x - current.right;
+            let same_style = current.item.font == style.font
+                && (current.item.font_size - font_size).abs() < 0.5
+                && current.item.fill_color == style.fill_color
+                && current.item.render_mode == style.render_mode
+                && current.item.highlighted == highlighted
+                && current.item.angle == angle
+                && current.in_form == style.in_form;
+            let same_line = (current.baseline - (glyph.y - glyph.height)).abs() < font_size * 0.5;
+            // Far apart on the line, e.g. table cells, stays separate as in `CompactLines`
+            let separate = gap > (font_size * 2.0).max(30.0);
+            if same_style && same_line && !separate {
+                let text = &mut current.item.text;
+                // Ideographs are never spaced, even where pdfium generated a space at a gap
+                if current.generated_space
+                    && text.ends_with(' ')
+                    && is_cjk(c)
+                    && text.trim_end().chars().last().is_some_and(is_cjk)
+                {
+                    text.pop();
+                }
+                text.push(c);
+                current.extend(&glyph);
+                continue;
+            }
+            found.extend(run.take().map(CharRun::finish));
+        }
+
+        run = Some(CharRun {
+            item: TextItem {
+                text: c.to_string(),
+                x: glyph.x,
+                y: glyph.y,
+                width: 0.0,
+                height: 0.0,
+                font: style.font.clone(),
+                font_size,
+                format: None,
+                font_properties: style.font_properties,
+                fill_color: style.fill_color,
+                stroke_color: style.stroke_color,
+                render_mode: style.render_mode,
+                highlighted,
+                angle,
+                vertical: false,
+            },
+            right: glyph.x + glyph.width,
+            bottom: glyph.y - glyph.height,
+            baseline: glyph.y - glyph.height,
+            in_form: style.in_form,
+            generated_space: false,
+        });
+    }
+    found.extend(run.take().map(CharRun::finish_line));
+
+    keep_form_text(found, xobject_text)
+}
+
+/// Style of a text object, shared by all of its glyphs in `extract_text_items_by_char`.
+struct ObjectStyle {
+    /// Bounds of the object, which tell it from the next one. pdfium drops duplicate objects
+    /// drawn over each other from the text page, so neighbours never have equal bounds.
+    bounds: Option<PdfQuadPoints>,
+    font: String,
+    font_properties: FontProperties,
+    fill_color: Option<Color>,
+    stroke_color: Option<Color>,
+    render_mode: RenderMode,
+    angle: f64,
+    in_form: bool,
+}
+
+impl ObjectStyle {
+    /// Reads the style of `text_object`, whose first glyph is centered on `center`. Objects in a
+    /// form XObject take the matrix of the innermost form under that glyph.
+    fn new(
+        text_object: &PdfPageTextObject,
+        bounds: Option<PdfQuadPoints>,
+        forms: &[(PdfRect, PdfMatrix)],
+        center: (PdfPoints, PdfPoints),
+    ) -> Self {
+        let form = forms
+            .iter()
+            .rev()
+            .find(|(rect, _)| rect.contains(center.0, center.1));
+        let font = text_object.font();
+        ObjectStyle {
+            bounds,
+            font: font.name(),
+            font_properties: font_properties(&font),
+            fill_color: text_object.fill_color().ok().map(color),
+            stroke_color: text_object.stroke_color().ok().map(color),
+            render_mode: render_mode(text_object.render_mode()),
+            angle: text_angle(
+                text_object,
+                form.map_or(PdfMatrix::IDENTITY, |(_, matrix)| *matrix),
+            ),
+            in_form: form.is_some(),
+        }
+    }
+}
+
+/// Page-space region and matrix of every form XObject on the page, nested forms after the
+/// forms that contain them. pdfium reads form text into the text page as well, so glyphs are
+/// matched to their form by position.
+fn form_regions(page: &PdfPage) -> Vec<(PdfRect, PdfMatrix)> {
+    let mut regions = Vec::new();
+    for object in page.objects().iter() {
+        collect_form_regions(&object, PdfMatrix::IDENTITY, &mut regions);
+    }
+    regions
+}
+
+/// Adds `object`, if it is a form, and the forms nested in it to `regions`; `parent` maps the
+/// enclosing form to page space, as in `collect_form_text`.
+fn collect_form_regions(
+    object: &PdfPageObject,
+    parent: PdfMatrix,
+    regions: &mut Vec<(PdfRect, PdfMatrix)>,
+) {
+    let (Some(form), Ok(bounds)) = (object.as_x_object_form_object(), object.bounds()) else {
+        return;
+    };
+    let matrix = form
+        .matrix()
+        .map_or(parent, |matrix| matrix.multiply(parent));
+    regions.push((bounds.transform(parent).to_rect(), matrix));
+    for index in form.as_range() {
+        if let Ok(nested) = form.get(index) {
+            collect_form_regions(&nested, matrix, regions);
+        }
+    }
+}
+
+/// Characters collected into one text item by `extract_text_items_by_char`. Positions are in
+/// the frame of the run's baseline until it is finished.
+struct CharRun {
+    item: TextItem,
+    right: f64,
+    bottom: f64,
+    /// Bottom of the first glyph, to tell a new line from descenders
+    baseline: f64,
+    in_form: bool,
+    /// The trailing space was generated by pdfium rather than read from the content
+    generated_space: bool,
+}
+
+impl CharRun {
+    fn extend(&mut self, glyph: &BoundingBox) {
+        self.item.x = self.item.x.min(glyph.x);
+        self.item.y = self.item.y.max(glyph.y);
+        self.right = self.right.max(glyph.x + glyph.width);
+        self.bottom = self.bottom.min(glyph.y - glyph.height);
+    }
+
+    /// Ends a run at a style change, keeping a trailing word space for the next run.
+    fn finish(mut self) -> (TextItem, bool) {
+        let bbox = BoundingBox {
+            x: self.item.x,
+            y: self.item.y,
+            width: self.right - self.item.x,
+            height: self.item.y - self.bottom,
+        }
+        .out_of_text_frame(self.item.angle);
+        self.item.x = bbox.x;
+        self.item.y = bbox.y;
+        self.item.width = bbox.width;
+        self.item.height = bbox.height;
+        (self.item, self.in_form)
+    }
+
+    /// Ends the last run of a line.
+    fn finish_line(mut self) -> (TextItem, bool) {
+        self.item.text.truncate(self.item.text.trim_end().len());
+        self.finish()
+    }
+}
+
+/// Collects the text objects of a form XObject and of the forms nested in it. Their bounds are
+/// in form space, so the forms' matrices are applied on the way down; `parent` maps the
+/// enclosing form to page space.
+fn collect_form_text(
+    form: &PdfPageXObjectFormObject,
+    parent: PdfMatrix,
+    highlights: &[PdfRect],
+    found: &mut Vec<(TextItem, bool)>,
+) {
+    let matrix = form
+        .matrix()
+        .map_or(parent, |matrix| matrix.multiply(parent));
+    for index in form.as_range() {
+        let Ok(object) = form.get(index) else {
+            continue;
+        };
+        if let Some(text_object) = object.as_text_object() {
+            if let Some(item) = text_item(text_object, matrix, highlights) {
+                found.push((item, true));
+            }
+        } else if let Some(nested) = object.as_x_object_form_object() {
+            collect_form_text(nested, matrix, highlights, found);
+        }
+    }
+}
+
+/// Builds a text item from a text object, mapping its bounds to page space with `matrix`.
+fn text_item(
+    text_object: &PdfPageTextObject,
+    matrix: PdfMatrix,
+    highlights: &[PdfRect],
+) -> Option<TextItem> {
+    let text = text_object.text();
+    if text.trim().is_empty() {
+        return None;
+    }
+
+    let font = text_object.font();
+    let font_name = font.name();
+    let bounds = text_object
+        .bounds()
+        .unwrap_or(PdfQuadPoints::zero())
+        .transform(matrix);
+
+    let width = (bounds.width().value).abs() as f64;
+    let height = (bounds.height().value).abs() as f64;
+
+    // Highlighted when the middle of the text lies inside a highlight annotation
+    let center_x = bounds.left() + bounds.width() / 2.0;
+    let center_y = bounds.bottom() + bounds.height() / 2.0;
+    let highlighted = highlights
+        .iter()
+        .any(|rect| rect.contains(center_x, center_y));
+
+    // A form scaled down on the page also scales the size of its text
+    let vertical_scale = (matrix.c() * matrix.c() + matrix.d() * matrix.d()).sqrt() as f64;
+    let angle = text_angle(text_object, matrix);
+
+    Some(TextItem {
+        text,
+        x: bounds.left().value as f64,
+        y: bounds.top().value as f64,
+        width,
+        height,
+        font: font_name,
+        font_size: text_object.scaled_font_size().value as f64 * vertical_scale,
+        format: None,
+        font_properties: font_properties(&font),
+        fill_color: text_object.fill_color().ok().map(color),
+        stroke_color: text_object.stroke_color().ok().map(color),
+        render_mode: render_mode(text_object.render_mode()),
+        highlighted,
+        angle,
+        vertical: false,
+    })
+}
+
+/// Baseline direction of a text object in page space, in degrees counterclockwise. `parent`
+/// maps the enclosing form, if any, to page space.
+fn text_angle(text_object: &PdfPageTextObject, parent: PdfMatrix) -> f64 {
+    let matrix = text_object
+        .matrix()
+        .map_or(parent, |matrix| matrix.multiply(parent));
+    let angle = (matrix.b() as f64).atan2(matrix.a() as f64).to_degrees();
+    // Snap float noise so horizontal text compares equal
+    (angle * 100.0).round().rem_euclid(36000.0) / 100.0
+}
+
+/// Regions of the page's highlight annotations.
+fn highlight_regions(page: &PdfPage) -> Vec<PdfRect> {
+    page.annotations()
+        .iter()
+        .filter(|annotation| annotation.annotation_type() == PdfPageAnnotationType::Highlight)
+        .filter_map(|annotation| annotation.bounds().ok())
+        .collect()
+}
+
+fn extract_text_items(page: &PdfPage, xobject_text: XObjectText) -> Vec<ItemType> {
+    let highlights = highlight_regions(page);
+    // Content stream order, with whether each item came from a form XObject
+    let mut found: Vec<(TextItem, bool)> = Vec::new();
+
+    for object in page.objects().iter() {
+        if let Some(text_object) = object.as_text_object() {
+            if let Some(item) = text_item(text_object, PdfMatrix::IDENTITY, &highlights) {
+                found.push((item, false));
+            }
+        } else if let Some(form) = object.as_x_object_form_object() {
+            collect_form_text(form, PdfMatrix::IDENTITY, &highlights, &mut found);
+        }
+    }
+
+    let items = keep_form_text(found, xobject_text);
 
     // items.sort_by(|a, b| match (a, b) {
     //     (ItemType::TextItem(ta), ItemType::TextItem(tb)) => {
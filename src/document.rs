@@ -0,0 +1,393 @@
+use crate::models::{BlockType, BoundingBox, ItemType, LineItem, ParseResult, TocList};
+use crate::transformations::common::{
+    caption_label, cjk_boundary, clean_line_text, ends_sentence, join_line_items,
+    scanned_placeholder, strip_list_marker,
+};
+use serde::Serialize;
+
+/// Hierarchical view of a converted PDF, built from the final `ParseResult`
+/// before any renderer flattens it.
+#[derive(Debug, Serialize)]
+pub struct Document {
+    pub page_count: usize,
+    /// Content that appears before the first heading.
+    pub blocks: Vec<Block>,
+    pub sections: Vec<Section>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Section {
+    pub heading: Heading,
+    pub blocks: Vec<Block>,
+    pub sections: Vec<Section>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Heading {
+    pub level: usize,
+    pub text: String,
+    pub provenance: Vec<Provenance>,
+}
+
+/// Region of a page that contributed to a node. Lines on the same page are merged into one box.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Provenance {
+    pub page: u16,
+    pub bbox: BoundingBox,
+    /// Text from an OCR layer kept with `--ocr-pages low-confidence`
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub low_confidence: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Block {
+    Paragraph {
+        text: String,
+        provenance: Vec<Provenance>,
+    },
+    List {
+        items: Vec<ListEntry>,
+    },
+    Code {
+        text: String,
+        provenance: Vec<Provenance>,
+    },
+    Toc {
+        list: TocList,
+        entries: Vec<TocEntry>,
+    },
+    Footnote {
+        text: String,
+        provenance: Vec<Provenance>,
+    },
+    /// Figure or table caption, with the image region it belongs to when one was found.
+    Caption {
+        list: TocList,
+        number: String,
+        text: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        figure: Option<BoundingBox>,
+        provenance: Vec<Provenance>,
+    },
+    /// Table rows as printed, one cell per column; cells a row leaves blank are empty.
+    Table {
+        rows: Vec<Vec<String>>,
+        provenance: Vec<Provenance>,
+    },
+    /// Stands in for a scanned page whose text could not be recognized.
+    ScannedPage {
+        page: u16,
+        text: String,
+    },
+}
+
+#[derive(Debug, Serialize)]
+pub struct ListEntry {
+    /// Item text without its bullet or number
+    pub text: String,
+    /// Number of an ordered item
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub number: Option<u32>,
+    pub provenance: Provenance,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TocEntry {
+    pub level: usize,
+    pub text: String,
+    /// Anchor slug of the heading the entry points to, if it was found.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    pub provenance: Provenance,
+}
+
+enum Node {
+    Heading(Heading),
+    Block(Block),
+}
+
+impl Document {
+    pub fn from_parse_result(result: &ParseResult) -> Self {
+        let nodes = collect_nodes(result);
+        let (blocks, sections) = nest_sections(nodes);
+
+        Document {
+            page_count: result.pages.len(),
+            blocks,
+            sections,
+        }
+    }
+}
+
+/// Walks all lines in reading order and groups consecutive lines into flat heading/block nodes.
+fn collect_nodes(result: &ParseResult) -> Vec<Node> {
+    let paragraph_gap = result.globals.most_used_distance * 1.1;
+    let mut nodes: Vec<Node> = Vec::new();
+    let mut last_pos: Option<(u16, f64)> = None;
+    let mut last_hyphenated = false;
+
+    for page in &result.pages {
+        let has_lines = page
+            .items
+            .iter()
+            .any(|item| matches!(item, ItemType::LineItem(_)));
+        if page.scanned && !has_lines {
+            nodes.push(Node::Block(Block::ScannedPage {
+                page: page.index,
+                text: scanned_placeholder(page.index),
+            }));
+            last_hyphenated = false;
+        }
+
+        for item in &page.items {
+            let ItemType::LineItem(line) = item else {
+                continue;
+            };
+
+            let provenance = Provenance {
+                page: page.index,
+                bbox: BoundingBox::of_line(line),
+                low_confidence: page.low_confidence,
+            };
+            let raw = join_line_items(line);
+            let hyphenated = raw.trim_end().ends_with('-');
+
+            // Same page and within normal line distance, or a soft wrap across a page break
+            let continues = match (last_pos, nodes.last()) {
+                (Some((last_page, last_y)), Some(Node::Block(block))) => {
+                    if last_page == page.index {
+                        (last_y - line.y).abs() <= paragraph_gap
+                    } else {
+                        !ends_sentence(block_text(block))
+                    }
+                }
+                _ => false,
+            };
+            last_pos = Some((page.index, line.y));
+
+            if let Some(level) = line.block_type.heading_level() {
+                nodes.push(Node::Heading(Heading {
+                    level,
+                    text: clean_line_text(raw),
+                    provenance: vec![provenance],
+                }));
+                last_hyphenated = false;
+                continue;
+            }
+
+            match (line.block_type, nodes.last_mut()) {
+                (BlockType::TocItem(level), Some(Node::Block(Block::Toc { list, entries })))
+                    if *list == toc_list(line) =>
+                {
+                    entries.push(toc_entry(level, line, &raw, provenance));
+                }
+                (BlockType::TocItem(level), _) => {
+                    nodes.push(Node::Block(Block::Toc {
+                        list: toc_list(line),
+                        entries: vec![toc_entry(level, line, &raw, provenance)],
+                    }));
+                }
+                (
+                    BlockType::TableRow,
+                    Some(Node::Block(Block::Table {
+                        rows,
+                        provenance: p,
+                    })),
+                ) if rows.last().is_some_and(|row| row.len() == line.cells.len()) => {
+                    rows.push(line.cells.clone());
+                    extend_provenance(p, provenance);
+                }
+                (BlockType::TableRow, _) => {
+                    nodes.push(Node::Block(Block::Table {
+                        rows: vec![line.cells.clone()],
+                        provenance: vec![provenance],
+                    }));
+                }
+                (BlockType::ListItem, Some(Node::Block(Block::List { items }))) => {
+                    items.push(list_entry(line, provenance));
+                }
+                (BlockType::ListItem, _) => {
+                    nodes.push(Node::Block(Block::List {
+                        items: vec![list_entry(line, provenance)],
+                    }));
+                }
+                (
+                    BlockType::Code,
+                    Some(Node::Block(Block::Code {
+                        text,
+                        provenance: p,
+                    })),
+                ) if continues => {
+                    text.push('\n');
+                    text.push_str(&raw);
+                    extend_provenance(p, provenance);
+                }
+                (BlockType::Code, _) => {
+                    nodes.push(Node::Block(Block::Code {
+                        text: raw,
+                        provenance: vec![provenance],
+                    }));
+                }
+                (
+                    BlockType::Footnote,
+                    Some(Node::Block(Block::Footnote {
+                        text,
+                        provenance: p,
+                    })),
+                )
+                | (
+                    BlockType::Paragraph,
+                    Some(Node::Block(Block::Paragraph {
+                        text,
+                        provenance: p,
+                    })),
+                ) if continues => {
+                    if !last_hyphenated && !cjk_boundary(text, &raw) {
+                        text.push(' ');
+                    }
+                    text.push_str(&clean_line_text(raw));
+                    extend_provenance(p, provenance);
+                }
+                (
+                    BlockType::Caption,
+                    Some(Node::Block(Block::Caption {
+                        text,
+                        provenance: p,
+                        ..
+                    })),
+                ) if caption_label(&raw).is_none() => {
+                    if !last_hyphenated && !cjk_boundary(text, &raw) {
+                        text.push(' ');
+                    }
+                    text.push_str(&clean_line_text(raw));
+                    extend_provenance(p, provenance);
+                }
+                (BlockType::Caption, _) => {
+                    let text = clean_line_text(raw);
+                    match caption_label(&text) {
+                        Some(label) => nodes.push(Node::Block(Block::Caption {
+                            list: label.list,
+                            number: label.number,
+                            text,
+                            figure: line.figure,
+                            provenance: vec![provenance],
+                        })),
+                        None => nodes.push(Node::Block(Block::Paragraph {
+                            text,
+                            provenance: vec![provenance],
+                        })),
+                    }
+                }
+                (BlockType::Footnote, _) => {
+                    nodes.push(Node::Block(Block::Footnote {
+                        text: clean_line_text(raw),
+                        provenance: vec![provenance],
+                    }));
+                }
+                _ => {
+                    nodes.push(Node::Block(Block::Paragraph {
+                        text: clean_line_text(raw),
+                        provenance: vec![provenance],
+                    }));
+                }
+            }
+
+            last_hyphenated = hyphenated;
+        }
+    }
+
+    nodes
+}
+
+/// Turns the flat node list into nested sections using heading levels.
+fn nest_sections(nodes: Vec<Node>) -> (Vec<Block>, Vec<Section>) {
+    let mut preamble = Vec::new();
+    let mut sections = Vec::new();
+    let mut stack: Vec<Section> = Vec::new();
+
+    for node in nodes {
+        match node {
+            Node::Heading(heading) => {
+                while stack
+                    .last()
+                    .is_some_and(|open| open.heading.level >= heading.level)
+                {
+                    close_section(&mut stack, &mut sections);
+                }
+                stack.push(Section {
+                    heading,
+                    blocks: Vec::new(),
+                    sections: Vec::new(),
+                });
+            }
+            Node::Block(block) => match stack.last_mut() {
+                Some(open) => open.blocks.push(block),
+                None => preamble.push(block),
+            },
+        }
+    }
+
+    while !stack.is_empty() {
+        close_section(&mut stack, &mut sections);
+    }
+
+    (preamble, sections)
+}
+
+fn close_section(stack: &mut Vec<Section>, sections: &mut Vec<Section>) {
+    if let Some(section) = stack.pop() {
+        match stack.last_mut() {
+            Some(parent) => parent.sections.push(section),
+            None => sections.push(section),
+        }
+    }
+}
+
+fn extend_provenance(provenance: &mut Vec<Provenance>, next: Provenance) {
+    if let Some(last) = provenance.last_mut() {
+        if last.page == next.page {
+            last.bbox = last.bbox.union(&next.bbox);
+            return;
+        }
+    }
+    provenance.push(next);
+}
+
+fn list_entry(line: &LineItem, provenance: Provenance) -> ListEntry {
+    let mut line = line.clone();
+    let marker = strip_list_marker(&mut line);
+    ListEntry {
+        text: clean_line_text(join_line_items(&line)),
+        number: marker.and_then(|m| m.number),
+        provenance,
+    }
+}
+
+fn toc_list(line: &LineItem) -> TocList {
+    line.toc_target
+        .as_ref()
+        .map_or(TocList::Contents, |t| t.list)
+}
+
+fn toc_entry(level: usize, line: &LineItem, raw: &str, provenance: Provenance) -> TocEntry {
+    TocEntry {
+        level,
+        text: raw.split_whitespace().collect::<Vec<_>>().join(" "),
+        target: line.toc_target.as_ref().and_then(|t| t.slug.clone()),
+        provenance,
+    }
+}
+
+fn block_text(block: &Block) -> &str {
+    match block {
+        Block::Paragraph { text, .. }
+        | Block::Code { text, .. }
+        | Block::Footnote { text, .. }
+        | Block::Caption { text, .. } => text,
+        Block::List { .. }
+        | Block::Toc { .. }
+        | Block::Table { .. }
+        | Block::ScannedPage { .. } => "",
+    }
+}
@@ -1,12 +1,25 @@
+mod chunks;
 mod converter;
+mod document;
 mod logger;
 mod models;
+mod ocr;
 mod processor;
+mod site;
+mod split;
 mod transformations;
 
 use anyhow::Result;
+use chunks::{ChunkOptions, ChunkUnit, DEFAULT_CHUNK_OVERLAP, DEFAULT_CHUNK_SIZE};
 use clap::Parser;
+use converter::{ConvertOptions, OutputFormat, TextExtraction, XObjectText};
+use ocr::{OcrProvider, Tesseract};
+use processor::RunOptions;
+use site::SiteKind;
+use split::SplitBy;
 use std::path::PathBuf;
+use std::sync::Arc;
+use transformations::{compact_lines::RotatedText, detect_ocr_pages::OcrPolicy};
 
 /// pdf-to-md — быстрый конвертер PDF в Markdown с параллельной обработкой
 #[derive(Parser, Debug)]
@@ -23,7 +36,7 @@ struct Cli {
     #[arg(value_name = "INPUT")]
     input: PathBuf,
 
-    /// Output directory for generated .md files (default: current directory)
+    /// Output directory for generated files (default: current directory)
     #[arg(short = 'o', long = "output", value_name = "DIR")]
     output: Option<PathBuf>,
 
@@ -42,6 +55,90 @@ struct Cli {
     /// Write verbose logs to a file instead of stderr
     #[arg(short = 'l', long = "log-file", value_name = "FILE")]
     log_file: Option<PathBuf>,
+
+    /// Write a `<name>.map.json` source map next to each .md file
+    #[arg(long = "source-map")]
+    source_map: bool,
+
+    /// Output format
+    #[arg(short = 'f', long = "format", value_enum, default_value_t = OutputFormat::Markdown)]
+    format: OutputFormat,
+
+    /// Add per-page anchors (`#page-N`) to HTML output
+    #[arg(long = "page-anchors")]
+    page_anchors: bool,
+
+    /// Maximum chunk size for `--format chunks`
+    #[arg(long = "chunk-size", value_name = "N", default_value_t = DEFAULT_CHUNK_SIZE)]
+    chunk_size: usize,
+
+    /// Overlap between consecutive chunks, in the same unit as --chunk-size
+    #[arg(long = "chunk-overlap", value_name = "N", default_value_t = DEFAULT_CHUNK_OVERLAP)]
+    chunk_overlap: usize,
+
+    /// Unit for --chunk-size and --chunk-overlap
+    #[arg(long = "chunk-unit", value_enum, default_value_t = ChunkUnit::Tokens)]
+    chunk_unit: ChunkUnit,
+
+    /// Write one Markdown file per chapter, section or page into a directory per input
+    #[arg(long = "split-by", value_enum, value_name = "UNIT")]
+    split_by: Option<SplitBy>,
+
+    /// Generate an mdBook or MkDocs project with its images (split by h2 unless --split-by is
+    /// given)
+    #[arg(long = "site", value_enum, value_name = "KIND")]
+    site: Option<SiteKind>,
+
+    /// Insert a linked TOC built from the detected headings (at a `[TOC]` line, or at the top
+    /// if the PDF has no TOC)
+    #[arg(long = "generate-toc")]
+    generate_toc: bool,
+
+    /// Number of heading levels included by --generate-toc
+    #[arg(long = "toc-depth", value_name = "N", default_value_t = 3)]
+    toc_depth: usize,
+
+    /// Leading pages in which any TOC-like page is detected (default 20); TOCs further in
+    /// must start with a title such as "Contents" or "List of Figures"
+    #[arg(long = "toc-scan-pages", value_name = "N")]
+    toc_scan_pages: Option<usize>,
+
+    /// Keep every top-level heading at h1 (by default only a title at the start of the document
+    /// stays h1; other top-level headings move down a level with their subsections)
+    #[arg(long = "multiple-h1")]
+    multiple_h1: bool,
+
+    /// Add N levels to every heading, e.g. 1 to start at h2 when embedding into a larger site
+    #[arg(long = "heading-offset", value_name = "N", default_value_t = 0)]
+    heading_offset: usize,
+
+    /// Deepest heading level to emit (1-6); deeper headings are capped at this level
+    #[arg(long = "max-heading-level", value_name = "N")]
+    max_heading_level: Option<usize>,
+
+    /// What to do with text from OCR layers over scanned pages: use it, skip it, or keep it
+    /// flagged as low confidence
+    #[arg(long = "ocr-pages", value_enum, value_name = "POLICY", default_value_t = OcrPolicy::Use)]
+    ocr_pages: OcrPolicy,
+
+    /// Read text per text object (`objects`) or per character with pdfium's word spacing
+    /// (`chars`, for PDFs that place glyphs one by one or kern words apart)
+    #[arg(long = "extraction", value_enum, value_name = "MODE", default_value_t = TextExtraction::Objects)]
+    extraction: TextExtraction,
+
+    /// Non-horizontal text such as vertical sidebars: `group` it into lines along its own
+    /// baseline, or `exclude` it
+    #[arg(long = "rotated-text", value_enum, value_name = "MODE", default_value_t = RotatedText::Group)]
+    rotated_text: RotatedText,
+
+    /// Text inside form XObjects: `include` it, or `exclude` it on pages with text of their own
+    /// (stamps, stamped headers)
+    #[arg(long = "xobject-text", value_enum, value_name = "MODE", default_value_t = XObjectText::Include)]
+    xobject_text: XObjectText,
+
+    /// Tesseract language models for scanned pages, e.g. `eng+deu` (tesseract's default if unset)
+    #[arg(long = "ocr-lang", value_name = "LANGS")]
+    ocr_lang: Option<String>,
 }
 
 fn main() -> Result<()> {
@@ -57,14 +154,87 @@ fn main() -> Result<()> {
         anyhow::bail!("--name and --stdout cannot be used together");
     }
 
-    processor::run(
-        &cli.input,
-        cli.output.as_deref(),
-        cli.name.as_deref(),
-        cli.stdout,
-        cli.verbose,
-        cli.log_file.as_deref(),
-    )?;
+    if cli.source_map && cli.stdout {
+        anyhow::bail!("--source-map cannot be used with --stdout");
+    }
+
+    if cli.source_map && cli.format != OutputFormat::Markdown {
+        anyhow::bail!("--source-map is only supported with --format markdown");
+    }
+
+    if cli.page_anchors && cli.format != OutputFormat::Html {
+        anyhow::bail!("--page-anchors is only supported with --format html");
+    }
+
+    if let Some(flag) = cli
+        .site
+        .map(|_| "--site")
+        .or(cli.split_by.map(|_| "--split-by"))
+    {
+        if cli.stdout {
+            anyhow::bail!("{} cannot be used with --stdout", flag);
+        }
+        if cli.format != OutputFormat::Markdown {
+            anyhow::bail!("{} is only supported with --format markdown", flag);
+        }
+        if cli.source_map {
+            anyhow::bail!("{} and --source-map cannot be used together", flag);
+        }
+    }
+
+    if cli.toc_depth == 0 {
+        anyhow::bail!("--toc-depth must be greater than zero");
+    }
+
+    if cli
+        .max_heading_level
+        .is_some_and(|level| !(1..=6).contains(&level))
+    {
+        anyhow::bail!("--max-heading-level must be between 1 and 6");
+    }
+
+    if cli.chunk_size == 0 {
+        anyhow::bail!("--chunk-size must be greater than zero");
+    }
+
+    // Scanned pages are only recognized when their text is wanted
+    let ocr = (cli.ocr_pages != OcrPolicy::Skip)
+        .then(|| Tesseract::detect(cli.ocr_lang.clone()))
+        .flatten()
+        .map(|tesseract| Arc::new(tesseract) as Arc<dyn OcrProvider>);
+
+    let options = RunOptions {
+        output_dir: cli.output.as_deref(),
+        output_name: cli.name.as_deref(),
+        stdout: cli.stdout,
+        log_file: cli.log_file.as_deref(),
+        source_map: cli.source_map,
+        split_by: cli.split_by,
+        site: cli.site,
+        convert: ConvertOptions {
+            verbose: cli.verbose,
+            format: cli.format,
+            page_anchors: cli.page_anchors,
+            generate_toc: cli.generate_toc.then_some(cli.toc_depth),
+            toc_scan_pages: cli.toc_scan_pages,
+            multiple_h1: cli.multiple_h1,
+            heading_offset: cli.heading_offset,
+            max_heading_level: cli.max_heading_level,
+            ocr_pages: cli.ocr_pages,
+            extraction: cli.extraction,
+            xobject_text: cli.xobject_text,
+            rotated_text: cli.rotated_text,
+            ocr,
+            extract_images: cli.site.is_some(),
+            chunking: ChunkOptions {
+                max_size: cli.chunk_size,
+                overlap: cli.chunk_overlap,
+                unit: cli.chunk_unit,
+            },
+        },
+    };
+
+    processor::run(&cli.input, &options)?;
 
     Ok(())
 }
@@ -1,10 +1,41 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Page {
     pub index: u16,
     pub items: Vec<ItemType>,
+    /// Regions covered by image objects; used to place figure captions.
+    #[serde(default)]
+    pub images: Vec<BoundingBox>,
+    /// Image objects saved for the output, in page order. Only extracted when requested.
+    #[serde(default)]
+    pub figures: Vec<Figure>,
+    /// Regions covered by filled, non-white shapes; white text on them is still visible.
+    #[serde(default)]
+    pub shaded: Vec<BoundingBox>,
+    /// The page is a scanned image without a text layer of its own.
+    #[serde(default)]
+    pub scanned: bool,
+    /// The text comes from an OCR layer over a scanned image.
+    #[serde(default)]
+    pub ocr: bool,
+    /// The text should be flagged as low confidence in the output.
+    #[serde(default)]
+    pub low_confidence: bool,
+    /// Glyphs dropped because their font maps them to no Unicode character.
+    #[serde(default)]
+    pub unmapped_glyphs: usize,
+}
+
+/// An image object encoded as JPEG, to be written next to the Markdown that references it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Figure {
+    pub bbox: BoundingBox,
+    /// Path relative to the Markdown files, e.g. `assets/page-3-1.jpg`
+    pub path: String,
+    #[serde(skip)]
+    pub data: Vec<u8>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -12,9 +43,12 @@ pub enum ItemType {
     TextItem(TextItem),
     LineItem(LineItem),
     Markdown(String),
+    Html(String),
+    PlainText(String),
+    SourceMap(Vec<SourceSpan>),
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct TextItem {
     pub text: String,
     pub x: f64,
@@ -24,9 +58,77 @@ pub struct TextItem {
     pub font: String,
     pub font_size: f64,
     pub format: Option<WordFormat>,
+    pub font_properties: FontProperties,
+    pub fill_color: Option<Color>,
+    pub stroke_color: Option<Color>,
+    pub render_mode: RenderMode,
+    /// Covered by a highlight annotation
+    pub highlighted: bool,
+    /// Direction of the baseline in degrees counterclockwise from horizontal, in [0, 360), on
+    /// the page as displayed. Items of a rotated line are kept in the frame of that line.
+    #[serde(default)]
+    pub angle: f64,
+    /// Set in vertical writing mode: upright CJK glyphs that read top to bottom in columns.
+    /// Items of a vertical line are kept in the frame of their column, like rotated ones.
+    #[serde(default)]
+    pub vertical: bool,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl TextItem {
+    /// Runs left to right along a horizontal baseline, within a degree.
+    pub fn is_horizontal(&self) -> bool {
+        self.angle < 1.0 || self.angle > 359.0
+    }
+}
+
+/// RGBA color as reported by pdfium.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Color {
+    /// Perceived lightness, from 0.0 (black) to 1.0 (white).
+    pub fn lightness(&self) -> f64 {
+        (0.299 * f64::from(self.r) + 0.587 * f64::from(self.g) + 0.114 * f64::from(self.b)) / 255.0
+    }
+
+    /// Spread between the strongest and weakest channel; 0 for greys.
+    pub fn saturation(&self) -> u8 {
+        let max = self.r.max(self.g).max(self.b);
+        let min = self.r.min(self.g).min(self.b);
+        max - min
+    }
+}
+
+/// How a text object is painted, from its PDF text render mode. Clipping is ignored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum RenderMode {
+    #[default]
+    Fill,
+    Stroke,
+    FillStroke,
+    /// Neither filled nor stroked (render mode 3), as used for OCR text layers
+    Invisible,
+}
+
+/// Font descriptor values pdfium reports for a text object's font. Fonts without a descriptor
+/// (e.g. the standard 14) report no weight, and their flags cannot be trusted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct FontProperties {
+    /// 100-900, 400 being regular and 700 bold
+    pub weight: Option<u32>,
+    /// Degrees counter-clockwise from vertical; negative for fonts slanting right
+    pub italic_angle: i32,
+    pub italic: bool,
+    pub force_bold: bool,
+    pub fixed_pitch: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct LineItem {
     pub items: Vec<TextItem>,
     pub x: f64,
@@ -34,10 +136,121 @@ pub struct LineItem {
     pub width: f64,
     pub height: f64,
     pub block_type: BlockType,
+    /// Set on `TocItem` lines: where the entry points to.
+    pub toc_target: Option<TocTarget>,
+    /// Anchor id for a non-heading line that a TOC entry links to (e.g. a figure caption).
+    pub anchor: Option<String>,
+    /// Set on the first line of a `Caption`: the image region the caption describes, if any.
+    pub figure: Option<BoundingBox>,
+    /// Mostly right-to-left text; `items` are in logical (reading) order either way.
+    #[serde(default)]
+    pub rtl: bool,
+    /// Set on `TableRow` lines: the text of each column, empty where the row has no cell.
+    #[serde(default)]
+    pub cells: Vec<String>,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+/// Printed page number of a TOC entry and, once resolved, the anchor of its heading or caption.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TocTarget {
+    pub page_label: String,
+    pub slug: Option<String>,
+    pub list: TocList,
+}
+
+/// Which list a TOC entry belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TocList {
+    #[default]
+    Contents,
+    Figures,
+    Tables,
+}
+
+/// Axis-aligned box in PDF page coordinates: `x`/`y` is the top-left corner, `y` grows upwards.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BoundingBox {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl BoundingBox {
+    pub fn of_line(line: &LineItem) -> Self {
+        BoundingBox {
+            x: line.x,
+            y: line.y,
+            width: line.width,
+            height: line.height,
+        }
+    }
+
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        x >= self.x && x <= self.x + self.width && y <= self.y && y >= self.y - self.height
+    }
+
+    /// Box around this box turned clockwise by `angle` degrees about the origin, i.e. moved into
+    /// the frame of text whose baseline runs at `angle`, where that baseline is horizontal.
+    pub fn in_text_frame(&self, angle: f64) -> BoundingBox {
+        self.rotated(-angle)
+    }
+
+    /// Inverse of [`BoundingBox::in_text_frame`].
+    pub fn out_of_text_frame(&self, angle: f64) -> BoundingBox {
+        self.rotated(angle)
+    }
+
+    fn rotated(&self, angle: f64) -> BoundingBox {
+        let (sin, cos) = angle.to_radians().sin_cos();
+        let corners = [
+            (self.x, self.y),
+            (self.x + self.width, self.y),
+            (self.x, self.y - self.height),
+            (self.x + self.width, self.y - self.height),
+        ]
+        .map(|(x, y)| (x * cos - y * sin, x * sin + y * cos));
+        let left = corners.iter().map(|c| c.0).fold(f64::MAX, f64::min);
+        let right = corners.iter().map(|c| c.0).fold(f64::MIN, f64::max);
+        let top = corners.iter().map(|c| c.1).fold(f64::MIN, f64::max);
+        let bottom = corners.iter().map(|c| c.1).fold(f64::MAX, f64::min);
+        BoundingBox {
+            x: left,
+            y: top,
+            width: right - left,
+            height: top - bottom,
+        }
+    }
+
+    pub fn union(&self, other: &BoundingBox) -> BoundingBox {
+        let left = self.x.min(other.x);
+        let right = (self.x + self.width).max(other.x + other.width);
+        let top = self.y.max(other.y);
+        let bottom = (self.y - self.height).min(other.y - other.height);
+        BoundingBox {
+            x: left,
+            y: top,
+            width: right - left,
+            height: top - bottom,
+        }
+    }
+}
+
+/// Maps a range of output lines (1-based, inclusive) back to the page region it was rendered from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SourceSpan {
+    pub start_line: usize,
+    pub end_line: usize,
+    pub page: u16,
+    pub block_type: BlockType,
+    pub bbox: BoundingBox,
+    pub lines: Vec<BoundingBox>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
 pub enum BlockType {
+    #[default]
     Paragraph,
     H1,
     H2,
@@ -49,11 +262,36 @@ pub enum BlockType {
     ListItem,
     Footnote,
     TocItem(usize),
+    /// Figure or table caption ("Figure 3-2. Request flow").
+    Caption,
+    /// One row of a table. Consecutive rows with the same number of cells form one table.
+    TableRow,
 }
 
-impl Default for BlockType {
-    fn default() -> Self {
-        BlockType::Paragraph
+impl BlockType {
+    /// Heading depth (1-6) for `H1`..`H6`, `None` for every other block type.
+    pub fn heading_level(&self) -> Option<usize> {
+        match self {
+            BlockType::H1 => Some(1),
+            BlockType::H2 => Some(2),
+            BlockType::H3 => Some(3),
+            BlockType::H4 => Some(4),
+            BlockType::H5 => Some(5),
+            BlockType::H6 => Some(6),
+            _ => None,
+        }
+    }
+
+    /// `H1`..`H6` for a heading depth, clamped to that range.
+    pub fn heading(level: usize) -> BlockType {
+        match level {
+            0 | 1 => BlockType::H1,
+            2 => BlockType::H2,
+            3 => BlockType::H3,
+            4 => BlockType::H4,
+            5 => BlockType::H5,
+            _ => BlockType::H6,
+        }
     }
 }
 
@@ -0,0 +1,172 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+/// A page rendered for recognition: 8-bit RGBA pixels, row by row from the top left.
+pub struct PageImage {
+    pub width: usize,
+    pub height: usize,
+    /// Resolution the page was rendered at, in pixels per inch
+    pub dpi: f64,
+    pub rgba: Vec<u8>,
+}
+
+/// A recognized word. The box is in image pixels with the origin at the top left.
+#[derive(Debug, Clone)]
+pub struct OcrWord {
+    pub text: String,
+    pub left: f64,
+    pub top: f64,
+    pub width: f64,
+    pub height: f64,
+    /// Index of the text line the engine placed the word on, in reading order
+    pub line: usize,
+}
+
+/// An OCR engine that turns a rendered page into positioned words.
+pub trait OcrProvider: Send + Sync + std::fmt::Debug {
+    /// Short name used in log messages
+    fn name(&self) -> &str;
+    fn recognize(&self, image: &PageImage) -> Result<Vec<OcrWord>>;
+}
+
+/// Runs a locally installed `tesseract` binary and reads its TSV output.
+#[derive(Debug, Clone)]
+pub struct Tesseract {
+    pub command: PathBuf,
+    /// Language models passed as `-l`, e.g. `eng+deu` (tesseract's default if unset)
+    pub language: Option<String>,
+}
+
+impl Tesseract {
+    /// Finds `tesseract` on the `PATH`, or returns `None` if it cannot be run.
+    pub fn detect(language: Option<String>) -> Option<Self> {
+        let command = PathBuf::from("tesseract");
+        let available = Command::new(&command)
+            .arg("--version")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success());
+        available.then_some(Tesseract { command, language })
+    }
+}
+
+impl OcrProvider for Tesseract {
+    fn name(&self) -> &str {
+        "tesseract"
+    }
+
+    fn recognize(&self, image: &PageImage) -> Result<Vec<OcrWord>> {
+        let mut command = Command::new(&self.command);
+        command
+            .args(["stdin", "stdout", "--dpi"])
+            .arg(format!("{}", image.dpi.round()));
+        if let Some(language) = &self.language {
+            command.args(["-l", language]);
+        }
+        let mut child = command
+            .arg("tsv")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("Failed to run {}", self.command.display()))?;
+
+        // tesseract reads the whole image before it writes anything, so this cannot block. A
+        // failed write means tesseract exited early; its own message explains why.
+        let written = match child.stdin.take() {
+            Some(mut stdin) => stdin.write_all(&to_ppm(image)),
+            None => Ok(()),
+        };
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "tesseract failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        written.context("Failed to send the page image to tesseract")?;
+
+        Ok(parse_tsv(&String::from_utf8_lossy(&output.stdout)))
+    }
+}
+
+/// Encodes the image as a binary PPM, which tesseract reads without extra libraries.
+fn to_ppm(image: &PageImage) -> Vec<u8> {
+    let mut ppm = format!("P6\n{} {}\n255\n", image.width, image.height).into_bytes();
+    ppm.reserve(image.width * image.height * 3);
+    for pixel in image.rgba.chunks_exact(4) {
+        ppm.extend_from_slice(&pixel[..3]);
+    }
+    ppm
+}
+
+/// Reads the word rows (level 5) of tesseract's TSV output.
+fn parse_tsv(tsv: &str) -> Vec<OcrWord> {
+    let mut lines: HashMap<(&str, &str, &str, &str), usize> = HashMap::new();
+    let mut words = Vec::new();
+
+    for row in tsv.lines().skip(1) {
+        // level page block par line word left top width height conf text
+        let fields: Vec<&str> = row.splitn(12, '\t').collect();
+        if fields.len() < 12 || fields[0] != "5" {
+            continue;
+        }
+        let text = fields[11].trim();
+        if text.is_empty() {
+            continue;
+        }
+        let number = |i: usize| fields[i].trim().parse::<f64>().unwrap_or(0.0);
+        let next_line = lines.len();
+        let line = *lines
+            .entry((fields[1], fields[2], fields[3], fields[4]))
+            .or_insert(next_line);
+        words.push(OcrWord {
+            text: text.to_string(),
+            left: number(6),
+            top: number(7),
+            width: number(8),
+            height: number(9),
+            line,
+        });
+    }
+
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_words_and_lines_from_tesseract_tsv() {
+        let tsv = "level\tpage_num\tblock_num\tpar_num\tline_num\tword_num\tleft\ttop\twidth\theight\tconf\ttext\n\
+                   1\t1\t0\t0\t0\t0\t0\t0\t1700\t2200\t-1\t\n\
+                   4\t1\t1\t1\t1\t0\t100\t200\t400\t40\t-1\t\n\
+                   5\t1\t1\t1\t1\t1\t100\t200\t150\t40\t96.5\tScanned\n\
+                   5\t1\t1\t1\t1\t2\t270\t200\t230\t40\t91.2\tdocument\n\
+                   5\t1\t1\t1\t2\t1\t100\t260\t90\t40\t95.0\t \n\
+                   5\t1\t1\t1\t2\t2\t100\t260\t120\t40\t88.0\tpage\n";
+        let words = parse_tsv(tsv);
+        let summary: Vec<(&str, f64, usize)> = words
+            .iter()
+            .map(|w| (w.text.as_str(), w.left, w.line))
+            .collect();
+        // Only word rows with text; the blank word is skipped and lines are numbered in order
+        assert_eq!(
+            summary,
+            [
+                ("Scanned", 100.0, 0),
+                ("document", 270.0, 0),
+                ("page", 100.0, 1)
+            ]
+        );
+        assert_eq!(
+            (words[1].top, words[1].width, words[1].height),
+            (200.0, 230.0, 40.0)
+        );
+    }
+}
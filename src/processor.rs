@@ -3,18 +3,30 @@ use rayon::prelude::*;
 use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
 
+use crate::converter::ConvertOptions;
 use crate::logger::set_logger;
+use crate::site::{write_site, SiteKind};
+use crate::split::{index_markdown, split_markdown, SplitBy};
+
+/// Settings for a whole run, shared by every input file.
+pub struct RunOptions<'a> {
+    pub output_dir: Option<&'a Path>,
+    pub output_name: Option<&'a str>,
+    pub stdout: bool,
+    pub log_file: Option<&'a Path>,
+    pub source_map: bool,
+    /// Write one file per section/page into a directory per input instead of a single file
+    pub split_by: Option<SplitBy>,
+    /// Lay the split files out as an mdBook or MkDocs project
+    pub site: Option<SiteKind>,
+    pub convert: ConvertOptions,
+}
 
 /// Entry point for processing: handles single file or directory.
-pub fn run(
-    input: &Path,
-    output_dir: Option<&Path>,
-    output_name: Option<&str>,
-    stdout: bool,
-    verbose: bool,
-    log_file: Option<&Path>,
-) -> Result<()> {
-    if let Some(path) = log_file {
+pub fn run(input: &Path, options: &RunOptions) -> Result<()> {
+    let verbose = options.convert.verbose;
+
+    if let Some(path) = options.log_file {
         let file = std::fs::File::create(path)
             .with_context(|| format!("Failed to create log file: {}", path.display()))?;
         set_logger(file);
@@ -38,16 +50,7 @@ pub fn run(
     // Parallel processing with rayon across all CPU cores
     let results: Vec<Result<()>> = files
         .par_iter()
-        .map(|file_path| {
-            process_single_file(
-                file_path,
-                output_dir,
-                output_name,
-                stdout,
-                files.len(),
-                verbose,
-            )
-        })
+        .map(|file_path| process_single_file(file_path, options, files.len()))
         .collect();
 
     // Report errors
@@ -75,7 +78,7 @@ fn collect_pdf_files(input: &Path) -> Result<Vec<PathBuf>> {
     if input.is_file() {
         if input
             .extension()
-            .map_or(false, |ext| ext.eq_ignore_ascii_case("pdf"))
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("pdf"))
         {
             return Ok(vec![input.to_path_buf()]);
         } else {
@@ -89,7 +92,7 @@ fn collect_pdf_files(input: &Path) -> Result<Vec<PathBuf>> {
         if path.is_file()
             && path
                 .extension()
-                .map_or(false, |ext| ext.eq_ignore_ascii_case("pdf"))
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("pdf"))
         {
             pdf_files.push(path.to_path_buf());
         }
@@ -98,14 +101,8 @@ fn collect_pdf_files(input: &Path) -> Result<Vec<PathBuf>> {
 }
 
 /// Process a single PDF file: convert and either write to file or print to stdout.
-fn process_single_file(
-    input_path: &Path,
-    output_dir: Option<&Path>,
-    output_name: Option<&str>,
-    stdout: bool,
-    total_files: usize,
-    verbose: bool,
-) -> Result<()> {
+fn process_single_file(input_path: &Path, options: &RunOptions, total_files: usize) -> Result<()> {
+    let verbose = options.convert.verbose;
     let start = std::time::Instant::now();
 
     // Only print progress if processing multiple files or verbose
@@ -113,34 +110,85 @@ fn process_single_file(
         crate::lgger!("Converting: {}", input_path.display());
     }
 
-    let markdown = crate::converter::convert_file(input_path, verbose)
+    let conversion = crate::converter::convert_file(input_path, &options.convert)
         .with_context(|| format!("Failed to convert {}", input_path.display()))?;
 
-    if stdout {
+    if options.stdout {
         // For multiple files, add a header separator
         if total_files > 1 {
             println!("\n<!-- FILE: {} -->\n", input_path.display());
         }
-        println!("{}", markdown);
+        println!("{}", conversion.output);
         return Ok(());
     }
 
     // Determine output path
     let file_stem = input_path.file_stem().unwrap_or_default();
-    let name = output_name
+    let name = options
+        .output_name
         .map(PathBuf::from)
         .unwrap_or_else(|| PathBuf::from(file_stem));
 
-    let out_dir = output_dir
+    let out_dir = options
+        .output_dir
         .map(PathBuf::from)
         .unwrap_or_else(|| input_path.parent().unwrap_or(Path::new(".")).to_path_buf());
 
     std::fs::create_dir_all(&out_dir)?;
-    let output_path = out_dir.join(name).with_extension("md");
 
-    std::fs::write(&output_path, markdown)
+    // A site needs a nav, so it splits by chapter and section unless told otherwise
+    let split_by = options.split_by.or(options.site.map(|_| SplitBy::H2));
+    if let Some(split_by) = split_by {
+        let split_dir = out_dir.join(&name);
+        std::fs::create_dir_all(&split_dir)
+            .with_context(|| format!("Failed to create {}", split_dir.display()))?;
+
+        let parts = split_markdown(&conversion.output, &conversion.source_map, split_by);
+        let title = name.to_string_lossy();
+        if let Some(kind) = options.site {
+            write_site(&split_dir, kind, &title, &parts, &conversion.images)?;
+        } else {
+            for part in &parts {
+                let part_path = split_dir.join(&part.file_name);
+                std::fs::write(&part_path, &part.content)
+                    .with_context(|| format!("Failed to write to {}", part_path.display()))?;
+            }
+            let index_path = split_dir.join("index.md");
+            std::fs::write(&index_path, index_markdown(&title, &parts))
+                .with_context(|| format!("Failed to write to {}", index_path.display()))?;
+        }
+
+        if verbose || total_files > 1 {
+            crate::lgger!(
+                "Finished: {} ({} parts) in {:.2?}",
+                split_dir.display(),
+                parts.len(),
+                start.elapsed()
+            );
+        } else {
+            crate::lgger!("Created: {} ({} parts)", split_dir.display(), parts.len());
+        }
+        return Ok(());
+    }
+
+    let output_path = out_dir
+        .join(name)
+        .with_extension(options.convert.format.extension());
+
+    std::fs::write(&output_path, &conversion.output)
         .with_context(|| format!("Failed to write to {}", output_path.display()))?;
 
+    if options.source_map {
+        let map_path = output_path.with_extension("map.json");
+        let map = serde_json::json!({
+            "source": input_path.display().to_string(),
+            "output": output_path.display().to_string(),
+            "blocks": conversion.source_map,
+        });
+        std::fs::write(&map_path, serde_json::to_string_pretty(&map)?)
+            .with_context(|| format!("Failed to write to {}", map_path.display()))?;
+    }
+
     if verbose || total_files > 1 {
         let duration = start.elapsed();
         crate::lgger!("Finished: {} in {:.2?}", output_path.display(), duration);
@@ -0,0 +1,156 @@
+use crate::models::Figure;
+use crate::split::{index_markdown, Part};
+use anyhow::{Context, Result};
+use std::path::Path;
+
+/// Static site generator to lay out a split document for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum SiteKind {
+    /// `book.toml` + `src/SUMMARY.md`
+    Mdbook,
+    /// `mkdocs.yml` with a `nav` section + `docs/`
+    Mkdocs,
+}
+
+/// A part and the parts nested below it in the navigation.
+struct NavNode<'a> {
+    part: &'a Part,
+    children: Vec<NavNode<'a>>,
+}
+
+/// Writes a ready-to-build project for `kind` into `dir`, with the nav following heading levels.
+/// Images go under the content directory at the paths the parts reference them by.
+pub fn write_site(
+    dir: &Path,
+    kind: SiteKind,
+    title: &str,
+    parts: &[Part],
+    images: &[Figure],
+) -> Result<()> {
+    let content_dir = match kind {
+        SiteKind::Mdbook => dir.join("src"),
+        SiteKind::Mkdocs => dir.join("docs"),
+    };
+    std::fs::create_dir_all(&content_dir)
+        .with_context(|| format!("Failed to create {}", content_dir.display()))?;
+
+    for part in parts {
+        write(&content_dir.join(&part.file_name), &part.content)?;
+    }
+
+    for image in images {
+        let path = content_dir.join(&image.path);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        std::fs::write(&path, &image.data)
+            .with_context(|| format!("Failed to write to {}", path.display()))?;
+    }
+
+    let nav = nav_tree(parts);
+    match kind {
+        SiteKind::Mdbook => {
+            write(
+                &dir.join("book.toml"),
+                &format!(
+                    "[book]\ntitle = \"{}\"\nsrc = \"src\"\n",
+                    escape_quoted(title)
+                ),
+            )?;
+            let mut summary = String::from("# Summary\n\n");
+            summary_entries(&nav, 0, &mut summary);
+            write(&content_dir.join("SUMMARY.md"), &summary)?;
+        }
+        SiteKind::Mkdocs => {
+            write(&content_dir.join("index.md"), &index_markdown(title, parts))?;
+            let mut config = format!(
+                "site_name: \"{}\"\ndocs_dir: docs\nnav:\n  - Home: index.md\n",
+                escape_quoted(title)
+            );
+            mkdocs_entries(&nav, 1, &mut config);
+            write(&dir.join("mkdocs.yml"), &config)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn write(path: &Path, content: &str) -> Result<()> {
+    std::fs::write(path, content).with_context(|| format!("Failed to write to {}", path.display()))
+}
+
+/// Nests parts by heading level. A part never sits more than one level below the previous one,
+/// so a document that opens with an h2 still produces a valid tree.
+fn nav_tree(parts: &[Part]) -> Vec<NavNode<'_>> {
+    let min_level = parts
+        .iter()
+        .map(|p| p.level)
+        .filter(|&l| l > 0)
+        .min()
+        .unwrap_or(1);
+
+    let mut roots: Vec<NavNode> = Vec::new();
+    let mut last_depth: Option<usize> = None;
+    for part in parts {
+        let depth = part.level.saturating_sub(min_level);
+        let depth = last_depth.map_or(0, |last| depth.min(last + 1));
+        last_depth = Some(depth);
+
+        let mut siblings = &mut roots;
+        for _ in 0..depth {
+            siblings = &mut siblings
+                .last_mut()
+                .expect("depth is at most one below the previous part")
+                .children;
+        }
+        siblings.push(NavNode {
+            part,
+            children: Vec::new(),
+        });
+    }
+    roots
+}
+
+fn summary_entries(nodes: &[NavNode], depth: usize, summary: &mut String) {
+    for node in nodes {
+        summary.push_str(&format!(
+            "{}- [{}]({})\n",
+            "  ".repeat(depth),
+            escape_link_text(&node.part.title),
+            node.part.file_name
+        ));
+        summary_entries(&node.children, depth + 1, summary);
+    }
+}
+
+/// MkDocs sections cannot link a page themselves, so a part with children becomes a section
+/// whose first entry is the part's own page.
+fn mkdocs_entries(nodes: &[NavNode], depth: usize, config: &mut String) {
+    for node in nodes {
+        let indent = "  ".repeat(depth);
+        let title = escape_quoted(&node.part.title);
+        if node.children.is_empty() {
+            config.push_str(&format!(
+                "{}- \"{}\": {}\n",
+                indent, title, node.part.file_name
+            ));
+        } else {
+            config.push_str(&format!("{}- \"{}\":\n", indent, title));
+            config.push_str(&format!(
+                "{}  - \"{}\": {}\n",
+                indent, title, node.part.file_name
+            ));
+            mkdocs_entries(&node.children, depth + 1, config);
+        }
+    }
+}
+
+/// Escapes a string for a double-quoted TOML or YAML value.
+fn escape_quoted(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn escape_link_text(text: &str) -> String {
+    text.replace('[', "\\[").replace(']', "\\]")
+}
@@ -0,0 +1,197 @@
+use crate::models::SourceSpan;
+use crate::transformations::common::unique_slug;
+use std::collections::HashMap;
+
+/// Where to cut a Markdown document into separate files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum SplitBy {
+    /// One file per top-level heading
+    H1,
+    /// One file per first- or second-level heading
+    H2,
+    /// One file per PDF page
+    Page,
+}
+
+/// One output file of a split document.
+pub struct Part {
+    pub file_name: String,
+    pub title: String,
+    /// Heading level that opened the part (0 for front matter and pages), used to nest the index.
+    pub level: usize,
+    pub content: String,
+}
+
+struct Cut {
+    line: usize,
+    title: String,
+    level: usize,
+}
+
+/// Cuts `markdown` at the boundaries chosen by `split_by`, using the source map to locate
+/// headings and pages, and rewrites `](#slug)` links to point at the file holding the target.
+pub fn split_markdown(markdown: &str, source_map: &[SourceSpan], split_by: SplitBy) -> Vec<Part> {
+    let lines: Vec<&str> = markdown.lines().collect();
+    let cuts = find_cuts(&lines, source_map, split_by);
+
+    // Front matter before the first cut becomes its own part if it has any content
+    let mut ranges: Vec<(usize, usize, String, usize)> = Vec::new();
+    let first_cut = cuts.first().map_or(lines.len(), |c| c.line);
+    if lines[..first_cut].iter().any(|l| !l.trim().is_empty()) {
+        ranges.push((0, first_cut, "Front matter".to_string(), 0));
+    }
+    for (i, cut) in cuts.iter().enumerate() {
+        let end = cuts.get(i + 1).map_or(lines.len(), |next| next.line);
+        ranges.push((cut.line, end, cut.title.clone(), cut.level));
+    }
+
+    let width = ranges.len().to_string().len().max(2);
+    let mut used_names: HashMap<String, usize> = HashMap::new();
+    let mut parts: Vec<Part> = ranges
+        .into_iter()
+        .enumerate()
+        .map(|(i, (start, end, title, level))| {
+            let stem = unique_slug(&mut used_names, &title);
+            let stem = if stem.is_empty() {
+                "part".to_string()
+            } else {
+                stem
+            };
+            Part {
+                file_name: format!("{:0width$}-{}.md", i + 1, stem, width = width),
+                title,
+                level,
+                content: format!("{}\n", lines[start..end].join("\n").trim_matches('\n')),
+            }
+        })
+        .collect();
+
+    rewrite_links(&mut parts);
+    parts
+}
+
+/// Builds `index.md` with a link to every part.
+pub fn index_markdown(title: &str, parts: &[Part]) -> String {
+    let mut index = format!("# {}\n\n", title);
+    let min_level = parts
+        .iter()
+        .map(|p| p.level)
+        .filter(|&l| l > 0)
+        .min()
+        .unwrap_or(1);
+    for part in parts {
+        let depth = part.level.saturating_sub(min_level);
+        index.push_str(&format!(
+            "{}- [{}]({})\n",
+            "  ".repeat(depth),
+            part.title,
+            part.file_name
+        ));
+    }
+    index
+}
+
+fn find_cuts(lines: &[&str], source_map: &[SourceSpan], split_by: SplitBy) -> Vec<Cut> {
+    let mut cuts: Vec<Cut> = Vec::new();
+
+    for span in source_map {
+        // Source map lines are 1-based
+        let line = span.start_line.saturating_sub(1);
+        match split_by {
+            SplitBy::Page => {
+                if cuts.last().map(|c| c.title.clone()) == Some(page_title(span.page)) {
+                    continue;
+                }
+                // Keep an opening code fence with the block it belongs to
+                let line = if line > 0 && lines.get(line - 1) == Some(&"```") {
+                    line - 1
+                } else {
+                    line
+                };
+                cuts.push(Cut {
+                    line,
+                    title: page_title(span.page),
+                    level: 0,
+                });
+            }
+            SplitBy::H1 | SplitBy::H2 => {
+                let max_level = if split_by == SplitBy::H1 { 1 } else { 2 };
+                let Some(level) = span.block_type.heading_level() else {
+                    continue;
+                };
+                if level > max_level {
+                    continue;
+                }
+                let title = lines
+                    .get(line)
+                    .map(|l| l.trim_start_matches('#').trim().to_string())
+                    .unwrap_or_default();
+                cuts.push(Cut { line, title, level });
+            }
+        }
+    }
+
+    cuts.sort_by_key(|c| c.line);
+    cuts.dedup_by_key(|c| c.line);
+    cuts
+}
+
+fn page_title(page: u16) -> String {
+    format!("Page {}", page + 1)
+}
+
+/// Rewrites in-document `](#slug)` links. Heading slugs are recomputed as a Markdown viewer
+/// would: unique across the whole document before splitting, unique per file afterwards.
+fn rewrite_links(parts: &mut [Part]) {
+    let mut global_seen: HashMap<String, usize> = HashMap::new();
+    let mut targets: HashMap<String, (String, String)> = HashMap::new();
+
+    for part in parts.iter() {
+        let mut local_seen: HashMap<String, usize> = HashMap::new();
+        let mut in_code = false;
+        for line in part.content.lines() {
+            if line.starts_with("```") {
+                in_code = !in_code;
+            }
+            if in_code {
+                continue;
+            }
+            // Explicit anchors (e.g. on figure captions) keep their id in every file
+            for (pos, _) in line.match_indices("<a id=\"") {
+                let rest = &line[pos + 7..];
+                if let Some(end) = rest.find('"') {
+                    let id = rest[..end].to_string();
+                    targets.insert(id.clone(), (part.file_name.clone(), id));
+                }
+            }
+            if !line.starts_with('#') {
+                continue;
+            }
+            let text = line.trim_start_matches('#').trim();
+            let global = unique_slug(&mut global_seen, text);
+            let local = unique_slug(&mut local_seen, text);
+            targets.insert(global, (part.file_name.clone(), local));
+        }
+    }
+
+    for part in parts.iter_mut() {
+        let mut rewritten = String::with_capacity(part.content.len());
+        let mut rest = part.content.as_str();
+        while let Some(pos) = rest.find("](#") {
+            rewritten.push_str(&rest[..pos + 2]);
+            rest = &rest[pos + 3..];
+            let end = rest.find(')').unwrap_or(rest.len());
+            let slug = &rest[..end];
+            match targets.get(slug) {
+                Some((file, local)) if *file == part.file_name => {
+                    rewritten.push_str(&format!("#{}", local))
+                }
+                Some((file, local)) => rewritten.push_str(&format!("{}#{}", file, local)),
+                None => rewritten.push_str(&format!("#{}", slug)),
+            }
+            rest = &rest[end..];
+        }
+        rewritten.push_str(rest);
+        part.content = rewritten;
+    }
+}
@@ -1,5 +1,613 @@
-use crate::models::ParseResult;
+use crate::models::{ItemType, LineItem, ParseResult, TextItem, TocList};
+use std::collections::HashMap;
 
 pub trait Transformation {
     fn transform(&self, result: &mut ParseResult);
 }
+
+/// Joins the text items of a line, inserting a space wherever the horizontal gap
+/// between neighbours is wider than a glyph gap.
+pub fn join_line_items(line: &LineItem) -> String {
+    join_line_items_with(line, |item| item.text.clone())
+}
+
+/// Like [`join_line_items`], but lets the caller render each item (e.g. to add emphasis markup).
+/// Spacing decisions are still made on the raw item text.
+pub fn join_line_items_with<F>(line: &LineItem, render: F) -> String
+where
+    F: Fn(&TextItem) -> String,
+{
+    let mut merged = String::new();
+    if !line.items.is_empty() {
+        let mut prev_item = &line.items[0];
+        merged.push_str(&render(prev_item));
+        for item in line.items.iter().skip(1) {
+            let gap = horizontal_gap(prev_item, item);
+            let glue_threshold = (prev_item.font_size * 0.2).max(3.0);
+            let starts_with_punct = item
+                .text
+                .trim()
+                .starts_with([',', '.', ':', ';', ')', ']', '?', '!']);
+            let ends_with_open_punct = prev_item.text.trim().ends_with(['(', '[']);
+
+            if gap > glue_threshold
+                && !cjk_boundary(&prev_item.text, &item.text)
+                && !prev_item.text.ends_with(' ')
+                && !item.text.starts_with(' ')
+                && !starts_with_punct
+                && !ends_with_open_punct
+            {
+                merged.push(' ');
+            }
+            merged.push_str(&render(item));
+            prev_item = item;
+        }
+    }
+    merged
+}
+
+/// Horizontal space between two items on a line, whichever side of `prev` `next` is on, so
+/// that right-to-left runs are measured like left-to-right ones. Negative when they overlap.
+pub fn horizontal_gap(prev: &TextItem, next: &TextItem) -> f64 {
+    (next.x - (prev.x + prev.width)).max(prev.x - (next.x + next.width))
+}
+
+/// Horizontal reading direction of text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Ltr,
+    Rtl,
+}
+
+/// Letters of right-to-left scripts (bidi classes R and AL): Hebrew, Arabic, Syriac, Thaana,
+/// NKo and their presentation forms.
+pub fn is_rtl_char(c: char) -> bool {
+    matches!(
+        c,
+        '\u{0590}'..='\u{08FF}'
+            | '\u{FB1D}'..='\u{FDFF}'
+            | '\u{FE70}'..='\u{FEFF}'
+            | '\u{10800}'..='\u{10FFF}'
+            | '\u{1E800}'..='\u{1EFFF}'
+    ) && c.is_alphabetic()
+}
+
+/// Direction of the majority of strongly directional letters in `text`, or `None` for text
+/// without letters, such as numbers and punctuation.
+pub fn strong_direction(text: &str) -> Option<Direction> {
+    let (mut rtl, mut ltr) = (0, 0);
+    for c in text.chars().filter(|c| c.is_alphabetic()) {
+        if is_rtl_char(c) {
+            rtl += 1;
+        } else {
+            ltr += 1;
+        }
+    }
+    match rtl.cmp(&ltr) {
+        std::cmp::Ordering::Greater => Some(Direction::Rtl),
+        std::cmp::Ordering::Less => Some(Direction::Ltr),
+        std::cmp::Ordering::Equal if rtl > 0 => Some(Direction::Rtl),
+        std::cmp::Ordering::Equal => None,
+    }
+}
+
+/// Han ideographs, kana, Bopomofo, CJK punctuation and full-width forms: scripts written
+/// without spaces between words. Hangul is excluded because Korean separates words.
+pub fn is_cjk(c: char) -> bool {
+    matches!(
+        c,
+        '\u{3000}'..='\u{303F}'
+            | '\u{3040}'..='\u{30FF}'
+            | '\u{3100}'..='\u{312F}'
+            | '\u{31A0}'..='\u{31FF}'
+            | '\u{3400}'..='\u{4DBF}'
+            | '\u{4E00}'..='\u{9FFF}'
+            | '\u{F900}'..='\u{FAFF}'
+            | '\u{FE30}'..='\u{FE4F}'
+            | '\u{FF00}'..='\u{FFEF}'
+            | '\u{20000}'..='\u{3FFFF}'
+    )
+}
+
+/// Whether `prev` and `next` meet between two CJK characters, where neither a word space nor
+/// a line break separates them. Latin words next to CJK text keep their spaces.
+pub fn cjk_boundary(prev: &str, next: &str) -> bool {
+    prev.trim_end().chars().last().is_some_and(is_cjk)
+        && next.trim_start().chars().next().is_some_and(is_cjk)
+}
+
+/// Wraps the non-whitespace part of `text` in `open`/`close`, keeping surrounding spaces outside.
+pub fn wrap_trimmed(text: &str, open: &str, close: &str) -> String {
+    let inner = text.trim();
+    if inner.is_empty() {
+        return text.to_string();
+    }
+    let leading = if text.starts_with(' ') { " " } else { "" };
+    let trailing = if text.ends_with(' ') { " " } else { "" };
+    format!("{}{}{}{}{}", leading, open, inner, close, trailing)
+}
+
+/// Whether text ends with sentence punctuation, i.e. a following line starts a new paragraph.
+pub fn ends_sentence(text: &str) -> bool {
+    text.trim_end()
+        .chars()
+        .last()
+        .is_some_and(|c| ".?!\"”’؟。！？」』".contains(c))
+}
+
+/// Removes a line-ending hyphen from the last text item so it is dropped even when that
+/// item is rendered with emphasis markup. Returns whether a hyphen was removed.
+pub fn strip_trailing_hyphen(line: &mut LineItem) -> bool {
+    if let Some(last) = line.items.last_mut() {
+        let trimmed_len = last.text.trim_end().len();
+        if last.text[..trimmed_len].ends_with('-') {
+            last.text.truncate(trimmed_len - 1);
+            return true;
+        }
+    }
+    false
+}
+
+/// Normalizes prose text: drops a trailing hyphen, collapses whitespace and
+/// tightens numeric ranges ("66 - 68" -> "66-68").
+pub fn clean_line_text(mut merged: String) -> String {
+    // Implement hyphen removal: if a line ends with a hyphen, remove it.
+    if merged.ends_with('-') {
+        merged.pop(); // Remove the hyphen
+    }
+
+    // Fix range spacing: "66 - 68" -> "66-68"
+    let cleaned = merged.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    // Regex-like replacement for "number - number"
+    // We'll do it simply with string replacement if it matches pattern
+    // For indices specifically:
+    let mut final_text = String::new();
+    let tokens: Vec<&str> = cleaned.split(' ').collect();
+    for (i, token) in tokens.iter().enumerate() {
+        if *token == "-" && i > 0 && i < tokens.len() - 1 {
+            let prev = tokens[i - 1];
+            let next = tokens[i + 1];
+            if prev.chars().all(|c| c.is_numeric()) && next.chars().all(|c| c.is_numeric()) {
+                if !final_text.is_empty() {
+                    final_text.pop(); // Remove the space before '-'
+                }
+                final_text.push('-');
+                continue;
+            }
+        }
+        if i > 0 && !final_text.ends_with('-') {
+            final_text.push(' ');
+        }
+        final_text.push_str(token);
+    }
+    final_text
+}
+
+/// GitHub-style heading slug: lowercase, spaces become `-`, punctuation is dropped.
+pub fn slugify(text: &str) -> String {
+    text.trim()
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' {
+                Some(c)
+            } else if c.is_whitespace() {
+                Some('-')
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Slug for `text` that is unique within `seen`, suffixing repeats with `-1`, `-2`, ...
+pub fn unique_slug(seen: &mut HashMap<String, usize>, text: &str) -> String {
+    let base = slugify(text);
+    let count = seen.entry(base.clone()).or_insert(0);
+    let slug = if *count == 0 {
+        base
+    } else {
+        format!("{}-{}", base, count)
+    };
+    *count += 1;
+    slug
+}
+
+/// Anchor slug of every heading, keyed by `(page position, item position)`. Slugs are assigned
+/// in document order, the same way renderers and Markdown viewers do.
+pub fn heading_slugs(result: &ParseResult) -> HashMap<(usize, usize), String> {
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    let mut slugs = HashMap::new();
+    for (page_idx, page) in result.pages.iter().enumerate() {
+        for (item_idx, item) in page.items.iter().enumerate() {
+            if let ItemType::LineItem(line) = item {
+                if line.block_type.heading_level().is_some() {
+                    let text = clean_line_text(join_line_items(line));
+                    slugs.insert((page_idx, item_idx), unique_slug(&mut seen, &text));
+                }
+            }
+        }
+    }
+    slugs
+}
+
+/// Kind and number of a caption line such as "Figure 3-2. Request flow" or "Table 4: Limits".
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaptionLabel {
+    pub list: TocList,
+    pub number: String,
+}
+
+/// Parses a leading `Figure`/`Fig.`/`Table` label followed by a number like `3`, `3.2`, `3-2`
+/// or `A.1`.
+pub fn caption_label(text: &str) -> Option<CaptionLabel> {
+    let mut words = text.split_whitespace();
+    let list = match words.next()?.to_lowercase().as_str() {
+        "figure" | "fig." | "fig" => TocList::Figures,
+        "table" | "tab." => TocList::Tables,
+        _ => return None,
+    };
+    let token = words.next()?;
+    let number = token.trim_end_matches(['.', ':', ',', '\u{2014}', '\u{2013}']);
+    // "Figure 2 shows ..." is a sentence, "Figure 2 Request flow" a caption
+    let punctuated = number.len() < token.len();
+    if !punctuated
+        && words
+            .next()
+            .is_some_and(|next| next.starts_with(char::is_lowercase))
+    {
+        return None;
+    }
+    let mut parts = number.split(['.', '-', '\u{2013}']);
+    let first = parts.next()?;
+    let first_ok = first.chars().all(|c| c.is_ascii_digit())
+        || (first.len() == 1 && first.chars().all(|c| c.is_ascii_uppercase()));
+    if first.is_empty()
+        || !first_ok
+        || !parts.all(|p| !p.is_empty() && p.chars().all(|c| c.is_ascii_digit()))
+    {
+        return None;
+    }
+    Some(CaptionLabel {
+        list,
+        number: number.to_string(),
+    })
+}
+
+/// Anchor id for a caption, e.g. `figure-3-2`.
+pub fn caption_anchor(label: &CaptionLabel) -> String {
+    let kind = match label.list {
+        TocList::Tables => "table",
+        _ => "figure",
+    };
+    format!(
+        "{}-{}",
+        kind,
+        slugify(&label.number.replace(['.', '\u{2013}'], "-"))
+    )
+}
+
+/// Rewrites a caption's label punctuation to a colon: "Figure 3-2. Request flow" becomes
+/// "Figure 3-2: Request flow". Text without a caption label is returned unchanged.
+pub fn normalize_caption(text: &str) -> String {
+    let Some(label) = caption_label(text) else {
+        return text.to_string();
+    };
+    let mut words = text.split_whitespace();
+    let kind = words.next().unwrap_or_default();
+    words.next();
+    let rest = words.collect::<Vec<_>>().join(" ");
+    let rest = rest
+        .trim_start_matches(['.', ':', '\u{2014}', '\u{2013}', '-'])
+        .trim_start();
+    if rest.is_empty() {
+        format!("{} {}", kind, label.number)
+    } else {
+        format!("{} {}: {}", kind, label.number, rest)
+    }
+}
+
+/// Lowercase or uppercase roman numeral, as used for front-matter page numbers and parts.
+pub fn is_roman(text: &str) -> bool {
+    let is_lower = text.chars().all(|c| "ivxlcdm".contains(c));
+    let is_upper = text.chars().all(|c| "IVXLCDM".contains(c));
+    !text.is_empty() && text.len() <= 8 && (is_lower || is_upper)
+}
+
+/// Value of a roman numeral such as `xiv`, or `None` if `text` is not one.
+pub fn roman_value(text: &str) -> Option<i64> {
+    if !is_roman(text) {
+        return None;
+    }
+    let digit = |c: char| match c.to_ascii_lowercase() {
+        'i' => 1,
+        'v' => 5,
+        'x' => 10,
+        'l' => 50,
+        'c' => 100,
+        'd' => 500,
+        _ => 1000,
+    };
+    let digits: Vec<i64> = text.chars().map(digit).collect();
+    // A digit before a larger one is subtracted: "iv" is 4, "xc" is 90
+    let value = digits
+        .iter()
+        .enumerate()
+        .map(|(i, &d)| match digits.get(i + 1) {
+            Some(&next) if next > d => -d,
+            _ => d,
+        })
+        .sum();
+    Some(value)
+}
+
+/// Depth encoded by a heading's numbering: `Part II` is 0, `Chapter 4`, `Appendix A` and
+/// `4 Title` are 1, `4.2` and `A.1` are 2, `2.3.1` is 3. `None` for unnumbered text.
+pub fn section_depth(text: &str) -> Option<usize> {
+    let mut words = text.split_whitespace();
+    let first = words.next()?;
+    let second = words.next();
+
+    let is_ordinal = |word: &str| {
+        let word = word.trim_end_matches(['.', ':']);
+        (word.chars().all(|c| c.is_ascii_digit()) && !word.is_empty() && word.len() <= 3)
+            || is_roman(word)
+            || (word.len() == 1 && word.chars().all(|c| c.is_ascii_uppercase()))
+    };
+    match first.to_lowercase().as_str() {
+        "part" => return second.filter(|w| is_ordinal(w)).map(|_| 0),
+        "chapter" | "appendix" => return second.filter(|w| is_ordinal(w)).map(|_| 1),
+        _ => {}
+    }
+
+    // A number needs a title after it: "1.2" alone is more likely a figure or a page number
+    let title = second?;
+    if !title.starts_with(char::is_alphabetic) {
+        return None;
+    }
+    let number = first.trim_end_matches('.');
+    let parts: Vec<&str> = number.split('.').collect();
+    let first_ok = match parts[0] {
+        p if p.chars().all(|c| c.is_ascii_digit()) => !p.is_empty() && p.len() <= 2,
+        // Appendix numbering ("A.1") needs at least one numeric level
+        p => p.len() == 1 && p.chars().all(|c| c.is_ascii_uppercase()) && parts.len() > 1,
+    };
+    let rest_ok = parts[1..]
+        .iter()
+        .all(|p| !p.is_empty() && p.len() <= 3 && p.chars().all(|c| c.is_ascii_digit()));
+    (first_ok && rest_ok).then_some(parts.len())
+}
+
+/// Marker that opens a list item: a bullet, or the number of an ordered item.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListMarker {
+    /// The marker as printed, e.g. "•" or "3."
+    pub text: String,
+    /// Number of an ordered item; `None` for bullets
+    pub number: Option<u32>,
+}
+
+/// Bullet glyphs that open a list item even without a space after them.
+const BULLETS: &[char] = &[
+    '•', '◦', '▪', '▫', '‣', '⁃', '∙', '●', '○', '■', '□', '➢', '➤', '►',
+];
+
+/// Reads the list marker at the start of `text`: a bullet glyph, a dash or asterisk followed
+/// by a space, or a number of up to three digits followed by `.` or `)` and a space, as in
+/// "2. Install" or "(2) Install". Decimals such as "3.5 percent" are not markers.
+pub fn list_marker(text: &str) -> Option<ListMarker> {
+    let text = text.trim_start();
+    let first = text.chars().next()?;
+    let rest = &text[first.len_utf8()..];
+    let bullet = |text: &str| ListMarker {
+        text: text.to_string(),
+        number: None,
+    };
+
+    if BULLETS.contains(&first) {
+        return (!rest.trim().is_empty()).then(|| bullet(&text[..first.len_utf8()]));
+    }
+    if matches!(first, '-' | '–' | '—' | '*') {
+        let spaced = rest.starts_with(char::is_whitespace) && !rest.trim().is_empty();
+        return spaced.then(|| bullet(&text[..first.len_utf8()]));
+    }
+
+    let (open, body) = match text.strip_prefix('(') {
+        Some(body) => (true, body),
+        None => (false, text),
+    };
+    let digits = body.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits == 0 || digits > 3 {
+        return None;
+    }
+    let close = body[digits..].chars().next()?;
+    let closes = if open {
+        close == ')'
+    } else {
+        matches!(close, '.' | ')')
+    };
+    let after = &body[digits + 1..];
+    if !closes || !after.starts_with(char::is_whitespace) || after.trim().is_empty() {
+        return None;
+    }
+    let len = text.len() - after.len();
+    Some(ListMarker {
+        text: text[..len].to_string(),
+        number: body[..digits].parse().ok(),
+    })
+}
+
+/// Removes the list marker and the space after it from the start of a line, returning the
+/// marker. The marker may be an item of its own or the start of the first item.
+pub fn strip_list_marker(line: &mut LineItem) -> Option<ListMarker> {
+    let marker = list_marker(&join_line_items(line))?;
+    let mut remaining = marker.text.chars().count();
+    for item in &mut line.items {
+        let text = item.text.trim_start();
+        let taken: usize = text.chars().take(remaining).map(char::len_utf8).sum();
+        remaining -= text[..taken].chars().count();
+        item.text = text[taken..].trim_start().to_string();
+        if remaining == 0 && !item.text.is_empty() {
+            break;
+        }
+    }
+    line.items.retain(|item| !item.text.is_empty());
+    Some(marker)
+}
+
+/// One row of a Markdown table, `| a | b |`, with pipes inside cells escaped.
+pub fn markdown_table_row(cells: &[String]) -> String {
+    let cells: Vec<String> = cells.iter().map(|cell| cell.replace('|', "\\|")).collect();
+    format!("| {} |", cells.join(" | "))
+}
+
+/// Delimiter row that follows the first row of a Markdown table with `columns` columns.
+pub fn markdown_table_rule(columns: usize) -> String {
+    format!("|{}", " --- |".repeat(columns))
+}
+
+/// Marks the start of a page whose text is kept from an OCR layer with
+/// `--ocr-pages low-confidence`. Every renderer emits it in its own syntax.
+pub const LOW_CONFIDENCE_NOTE: &str = "low-confidence OCR text";
+
+/// Stands in for a scanned page whose text could not be recognized.
+pub fn scanned_placeholder(page_index: u16) -> String {
+    format!(
+        "Page {}: scanned image without a text layer",
+        page_index + 1
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(items: &[(&str, f64, f64)]) -> LineItem {
+        LineItem {
+            items: items
+                .iter()
+                .map(|&(text, x, width)| TextItem {
+                    text: text.to_string(),
+                    x,
+                    y: 700.0,
+                    width,
+                    height: 10.0,
+                    font_size: 10.0,
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn cjk_boundary_needs_cjk_on_both_sides() {
+        assert!(cjk_boundary("漢字", "です"));
+        assert!(cjk_boundary("文章。 ", " 次"));
+        assert!(!cjk_boundary("漢字", "means"));
+        assert!(!cjk_boundary("word", "漢字"));
+        assert!(!cjk_boundary("word", "means"));
+        // Korean separates words with spaces
+        assert!(!cjk_boundary("한국어", "문장"));
+    }
+
+    #[test]
+    fn joins_cjk_runs_without_spaces() {
+        let joined = join_line_items(&line(&[("日本語の", 0.0, 40.0), ("文章", 48.0, 20.0)]));
+        assert_eq!(joined, "日本語の文章");
+    }
+
+    #[test]
+    fn keeps_spaces_between_latin_and_cjk_words() {
+        let joined = join_line_items(&line(&[
+            ("The word", 0.0, 40.0),
+            ("漢字", 48.0, 20.0),
+            ("means", 76.0, 25.0),
+        ]));
+        assert_eq!(joined, "The word 漢字 means");
+    }
+
+    #[test]
+    fn reads_caption_labels() {
+        let label = caption_label("Figure 3-2. Request flow").unwrap();
+        assert_eq!(
+            (label.list, label.number.as_str()),
+            (TocList::Figures, "3-2")
+        );
+        assert_eq!(caption_anchor(&label), "figure-3-2");
+        let label = caption_label("Table A.1: Limits").unwrap();
+        assert_eq!(
+            (label.list, label.number.as_str()),
+            (TocList::Tables, "A.1")
+        );
+        assert!(caption_label("Fig. 7 Request flow").is_some());
+        // References in running text
+        assert_eq!(caption_label("Figure 2 shows the flow"), None);
+        assert_eq!(caption_label("Table of contents"), None);
+    }
+
+    #[test]
+    fn reads_section_depth_from_numbering() {
+        assert_eq!(section_depth("Part II Foundations"), Some(0));
+        assert_eq!(section_depth("Chapter 4: Storage"), Some(1));
+        assert_eq!(section_depth("Appendix A"), Some(1));
+        assert_eq!(section_depth("4 Storage"), Some(1));
+        assert_eq!(section_depth("4.2 Indexes"), Some(2));
+        assert_eq!(section_depth("A.1 Glossary"), Some(2));
+        assert_eq!(section_depth("2.3.1. Locking"), Some(3));
+        // A number without a title, a year, or a part without a number
+        assert_eq!(section_depth("1.2"), None);
+        assert_eq!(section_depth("2019 was a good year"), None);
+        assert_eq!(section_depth("Part of the problem"), None);
+    }
+
+    #[test]
+    fn reads_roman_numerals() {
+        assert_eq!(roman_value("iii"), Some(3));
+        assert_eq!(roman_value("xiv"), Some(14));
+        assert_eq!(roman_value("XC"), Some(90));
+        assert_eq!(roman_value("12"), None);
+        assert_eq!(roman_value("Xi"), None);
+    }
+
+    #[test]
+    fn reads_list_markers() {
+        let marker = |text: &str| list_marker(text).map(|m| (m.text, m.number));
+        assert_eq!(marker("• First"), Some(("•".to_string(), None)));
+        assert_eq!(marker("•First"), Some(("•".to_string(), None)));
+        assert_eq!(marker("- First"), Some(("-".to_string(), None)));
+        assert_eq!(marker("12. First"), Some(("12.".to_string(), Some(12))));
+        assert_eq!(marker("3) First"), Some(("3)".to_string(), Some(3))));
+        assert_eq!(marker("(4) First"), Some(("(4)".to_string(), Some(4))));
+        assert_eq!(marker("-5 degrees"), None);
+        assert_eq!(marker("3.5 percent"), None);
+        assert_eq!(marker("2024. A year"), None);
+        assert_eq!(marker("(4. First"), None);
+        assert_eq!(marker("1."), None);
+    }
+
+    #[test]
+    fn strips_list_markers_from_lines() {
+        let mut split = line(&[("1.", 0.0, 8.0), ("Install it", 14.0, 40.0)]);
+        let marker = strip_list_marker(&mut split).unwrap();
+        assert_eq!(marker.number, Some(1));
+        assert_eq!(join_line_items(&split), "Install it");
+
+        let mut joined = line(&[("• Install", 0.0, 40.0), ("it", 48.0, 8.0)]);
+        assert_eq!(strip_list_marker(&mut joined).unwrap().text, "•");
+        assert_eq!(join_line_items(&joined), "Install it");
+
+        let mut plain = line(&[("Install it", 0.0, 40.0)]);
+        assert_eq!(strip_list_marker(&mut plain), None);
+        assert_eq!(join_line_items(&plain), "Install it");
+    }
+
+    #[test]
+    fn ends_sentence_with_full_width_punctuation() {
+        assert!(ends_sentence("これは文です。"));
+        assert!(ends_sentence("本当ですか？"));
+        assert!(ends_sentence("「はい」"));
+        assert!(!ends_sentence("これは"));
+    }
+}
@@ -1,11 +1,25 @@
-use crate::models::{ItemType, LineItem, ParseResult, TextItem};
-use crate::transformations::common::Transformation;
+use crate::models::{BoundingBox, ItemType, LineItem, ParseResult, TextItem};
+use crate::transformations::common::{
+    cjk_boundary, horizontal_gap, strong_direction, Direction, Transformation,
+};
 use rayon::prelude::*;
 use std::cmp::Ordering;
 use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
 
+/// What to do with text that does not run along a horizontal baseline, such as vertical
+/// sidebar labels or rotated table headers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum RotatedText {
+    /// Build lines along the text's own baseline, after the page's horizontal lines
+    #[default]
+    Group,
+    /// Drop non-horizontal text
+    Exclude,
+}
+
 pub struct CompactLines {
     pub verbose: bool,
+    pub rotated_text: RotatedText,
 }
 
 impl Transformation for CompactLines {
@@ -13,6 +27,7 @@ impl Transformation for CompactLines {
         let most_used_distance = result.globals.most_used_distance;
 
         let counter = AtomicUsize::new(0);
+        let excluded = AtomicUsize::new(0);
         let total_pages = result.pages.len();
 
         let globals = &result.globals;
@@ -24,7 +39,7 @@ impl Transformation for CompactLines {
         result.pages.par_iter_mut().for_each(|page| {
             if self.verbose {
                 let c = counter.fetch_add(1, AtomicOrdering::Relaxed) + 1;
-                if c % 50 == 0 || c == total_pages {
+                if c.is_multiple_of(50) || c == total_pages {
                     crate::lgger!("CompactLines: Processed {}/{} pages...", c, total_pages);
                 }
             }
@@ -42,6 +57,11 @@ impl Transformation for CompactLines {
                 return;
             }
 
+            let (vertical, text_items): (Vec<TextItem>, Vec<TextItem>) =
+                text_items.into_iter().partition(|item| item.vertical);
+            let (text_items, rotated): (Vec<TextItem>, Vec<TextItem>) =
+                text_items.into_iter().partition(TextItem::is_horizontal);
+
             // Group by line
             let grouped_lines = group_items_by_line(text_items, most_used_distance);
 
@@ -53,11 +73,130 @@ impl Transformation for CompactLines {
                 }
             }
 
+            for line_item in vertical_lines(vertical, globals) {
+                new_items.push(ItemType::LineItem(line_item));
+            }
+
+            match self.rotated_text {
+                RotatedText::Group => {
+                    for line_item in rotated_lines(rotated, most_used_distance, globals) {
+                        new_items.push(ItemType::LineItem(line_item));
+                    }
+                }
+                RotatedText::Exclude => {
+                    excluded.fetch_add(rotated.len(), AtomicOrdering::Relaxed);
+                }
+            }
+
             page.items = new_items;
         });
+
+        if self.verbose && self.rotated_text == RotatedText::Exclude {
+            crate::lgger!(
+                "CompactLines: Excluded {} non-horizontal text items",
+                excluded.load(AtomicOrdering::Relaxed)
+            );
+        }
     }
 }
 
+/// Builds lines from non-horizontal text, one direction at a time. Items are moved into the
+/// frame of their baseline, where lines are grouped like horizontal ones; each line's box is
+/// then mapped back onto the page, while its items keep the baseline frame for spacing.
+fn rotated_lines(
+    items: Vec<TextItem>,
+    most_used_distance: f64,
+    globals: &crate::models::GlobalStats,
+) -> Vec<LineItem> {
+    let mut directions: Vec<(i64, Vec<TextItem>)> = Vec::new();
+    for mut item in items {
+        let framed = BoundingBox {
+            x: item.x,
+            y: item.y,
+            width: item.width,
+            height: item.height,
+        }
+        .in_text_frame(item.angle);
+        item.x = framed.x;
+        item.y = framed.y;
+        item.width = framed.width;
+        item.height = framed.height;
+
+        let direction = item.angle.round() as i64 % 360;
+        match directions.iter_mut().find(|(angle, _)| *angle == direction) {
+            Some((_, group)) => group.push(item),
+            None => directions.push((direction, vec![item])),
+        }
+    }
+
+    let mut lines = Vec::new();
+    for (angle, group) in directions {
+        for line_group in group_items_by_line(group, most_used_distance) {
+            if let Some(mut line) = create_line_item(line_group, globals) {
+                let bbox = BoundingBox::of_line(&line).out_of_text_frame(angle as f64);
+                line.x = bbox.x;
+                line.y = bbox.y;
+                line.width = bbox.width;
+                line.height = bbox.height;
+                lines.push(line);
+            }
+        }
+    }
+    lines
+}
+
+/// Builds one line per column of vertical writing. Columns are read right to left, each from
+/// top to bottom; the items are moved into the frame of text running down the page, where the
+/// column reads like a horizontal line, and the line's box is mapped back onto the page.
+fn vertical_lines(items: Vec<TextItem>, globals: &crate::models::GlobalStats) -> Vec<LineItem> {
+    let center = |item: &TextItem| item.x + item.width / 2.0;
+
+    let mut items = items;
+    items.sort_by(|a, b| center(b).partial_cmp(&center(a)).unwrap_or(Ordering::Equal));
+    let mut columns: Vec<Vec<TextItem>> = Vec::new();
+    for item in items {
+        match columns.last_mut() {
+            Some(column)
+                if (center(&column[0]) - center(&item)).abs()
+                    < column[0].font_size.max(1.0) * 0.5 =>
+            {
+                column.push(item)
+            }
+            _ => columns.push(vec![item]),
+        }
+    }
+
+    let mut lines = Vec::new();
+    for mut column in columns {
+        column.sort_by(|a, b| b.y.partial_cmp(&a.y).unwrap_or(Ordering::Equal));
+        for item in &mut column {
+            let framed = BoundingBox {
+                x: item.x,
+                y: item.y,
+                width: item.width,
+                height: item.height,
+            }
+            .in_text_frame(DOWNWARD);
+            item.x = framed.x;
+            item.y = framed.y;
+            item.width = framed.width;
+            item.height = framed.height;
+        }
+        if let Some(mut line) = create_line_item(column, globals) {
+            let bbox = BoundingBox::of_line(&line).out_of_text_frame(DOWNWARD);
+            line.x = bbox.x;
+            line.y = bbox.y;
+            line.width = bbox.width;
+            line.height = bbox.height;
+            lines.push(line);
+        }
+    }
+    lines
+}
+
+/// Direction of text running down the page, in degrees counterclockwise.
+const DOWNWARD: f64 = 270.0;
+
 fn group_items_by_line(items: Vec<TextItem>, most_used_distance: f64) -> Vec<Vec<TextItem>> {
     // items.sort_by(|a, b| b.y.partial_cmp(&a.y).unwrap_or(Ordering::Equal));
 
@@ -79,7 +218,7 @@ fn group_items_by_line(items: Vec<TextItem>, most_used_distance: f64) -> Vec<Vec
             };
 
             if (first.y - item.y).abs() > tolerance {
-                sort_line_by_x(&mut current_line);
+                order_line(&mut current_line);
                 lines.push(current_line);
                 current_line = Vec::new();
             }
@@ -88,17 +227,56 @@ fn group_items_by_line(items: Vec<TextItem>, most_used_distance: f64) -> Vec<Vec
     }
 
     if !current_line.is_empty() {
-        sort_line_by_x(&mut current_line);
+        order_line(&mut current_line);
         lines.push(current_line);
     }
 
     lines
 }
 
-fn sort_line_by_x(line: &mut Vec<TextItem>) {
+fn sort_line_by_x(line: &mut [TextItem]) {
     line.sort_by(|a, b| a.x.partial_cmp(&b.x).unwrap_or(Ordering::Equal));
 }
 
+/// Puts the items of a line in reading order. A right-to-left line reads from its rightmost
+/// item; runs in the other direction inside a line, such as Latin words or numbers in Hebrew
+/// or Arabic text and Arabic words in English text, keep their own order.
+fn order_line(line: &mut [TextItem]) {
+    sort_line_by_x(line);
+    let text: String = line.iter().map(|item| item.text.as_str()).collect();
+    let rtl = strong_direction(&text) == Some(Direction::Rtl);
+    if rtl {
+        line.reverse();
+    }
+
+    let embedded = if rtl { Direction::Ltr } else { Direction::Rtl };
+    let is_embedded = |item: &TextItem| match strong_direction(&item.text) {
+        Some(direction) => direction == embedded,
+        // Numbers read left to right within right-to-left text
+        None => rtl && item.text.chars().any(|c| c.is_numeric()),
+    };
+    let is_neutral = |item: &TextItem| strong_direction(&item.text).is_none() && !is_embedded(item);
+
+    let mut start = 0;
+    while start < line.len() {
+        if !is_embedded(&line[start]) {
+            start += 1;
+            continue;
+        }
+        // Neutral items between two embedded ones belong to the run
+        let mut last = start;
+        let mut next = start + 1;
+        while next < line.len() && (is_embedded(&line[next]) || is_neutral(&line[next])) {
+            if is_embedded(&line[next]) {
+                last = next;
+            }
+            next += 1;
+        }
+        line[start..=last].reverse();
+        start = last + 1;
+    }
+}
+
 fn create_line_item(
     items: Vec<TextItem>,
     globals: &crate::models::GlobalStats,
@@ -111,16 +289,17 @@ fn create_line_item(
     let mut current_item = items[0].clone();
 
     for item in items.into_iter().skip(1) {
-        let gap = item.x - (current_item.x + current_item.width);
+        let gap = horizontal_gap(&current_item, &item);
         let glue_threshold = (current_item.font_size * 0.2).max(5.0);
         let space_threshold = (current_item.font_size * 2.0).max(30.0);
-        let same_font = item.font == current_item.font;
+        // Highlighted and plain runs stay separate items so renderers can mark them
+        let same_font =
+            item.font == current_item.font && item.highlighted == current_item.highlighted;
 
         if gap <= glue_threshold && same_font {
             // Glue characters
             current_item.text.push_str(&item.text);
-            current_item.width = (item.x + item.width) - current_item.x;
-            current_item.height = current_item.height.max(item.height);
+            extend_item(&mut current_item, &item);
         } else if gap <= space_threshold && same_font {
             // Merge words with space
             // Check for punctuation to avoid unnecessary spaces
@@ -128,19 +307,23 @@ fn create_line_item(
                 .text
                 .chars()
                 .next()
-                .map_or(false, |c| ".,:;?!)]}".contains(c));
+                .is_some_and(|c| ".,:;?!)]}".contains(c));
             let is_current_open_punctuation = current_item
                 .text
                 .chars()
                 .last()
-                .map_or(false, |c| "([{".contains(c));
+                .is_some_and(|c| "([{".contains(c));
+
+            // Text read per character already carries its word spaces
+            let spaced = current_item.text.ends_with(' ') || item.text.starts_with(' ');
+            // Chinese and Japanese put no spaces between words, only kerning gaps
+            let cjk = cjk_boundary(&current_item.text, &item.text);
 
-            if !is_next_punctuation && !is_current_open_punctuation {
+            if !is_next_punctuation && !is_current_open_punctuation && !spaced && !cjk {
                 current_item.text.push(' ');
             }
             current_item.text.push_str(&item.text);
-            current_item.width = (item.x + item.width) - current_item.x;
-            current_item.height = current_item.height.max(item.height);
+            extend_item(&mut current_item, &item);
         } else {
             merged_text_items.push(current_item);
             current_item = item;
@@ -148,39 +331,26 @@ fn create_line_item(
     }
     merged_text_items.push(current_item);
 
-    // Apply formatting to the merged items
+    // Record formatting as metadata; renderers decide how to present it
     for item in &mut merged_text_items {
-        if let Some(format) = globals.font_to_format.get(&item.font) {
-            let inner_text = item.text.trim();
-            if inner_text.is_empty() {
-                continue; // Don't format whitespace-only strings
-            }
-
-            // Check for leading/trailing whitespace to recreate it outside formatting
-            let leading_space = if item.text.starts_with(' ') { " " } else { "" };
-            let trailing_space = if item.text.ends_with(' ') { " " } else { "" };
-
-            // We need to handle consecutive spaces if any?
-            // "  Word  " -> " " + "**Word**" + " "
-            // Re-construct logic:
-
-            let formatted_inner = match format {
-                crate::models::WordFormat::Bold => format!("**{}**", inner_text),
-                crate::models::WordFormat::Italic => format!("_{}_", inner_text),
-                crate::models::WordFormat::BoldItalic => format!("**_{}_**", inner_text),
-                _ => inner_text.to_string(),
-            };
-
-            item.text = format!("{}{}{}", leading_space, formatted_inner, trailing_space);
+        if item.text.trim().is_empty() {
+            continue; // Don't format whitespace-only strings
         }
+        item.format = globals.font_to_format.get(&item.font).copied();
     }
 
-    let x = merged_text_items.first().unwrap().x;
+    let x = merged_text_items
+        .iter()
+        .map(|i| i.x)
+        .fold(f64::MAX, f64::min);
     let y = merged_text_items.first().unwrap().y;
-
-    let last = merged_text_items.last().unwrap();
-    let first = merged_text_items.first().unwrap();
-    let width = (last.x + last.width) - first.x;
+    let right = merged_text_items
+        .iter()
+        .map(|i| i.x + i.width)
+        .fold(f64::MIN, f64::max);
+    let width = right - x;
+    let text: String = merged_text_items.iter().map(|i| i.text.as_str()).collect();
+    let rtl = strong_direction(&text) == Some(Direction::Rtl);
 
     let height = merged_text_items
         .iter()
@@ -194,5 +364,128 @@ fn create_line_item(
         width,
         height,
         block_type: crate::models::BlockType::Paragraph,
+        toc_target: None,
+        anchor: None,
+        figure: None,
+        rtl,
+        cells: Vec::new(),
     })
 }
+
+/// Grows `current` to cover `item`, which may lie on either side of it.
+fn extend_item(current: &mut TextItem, item: &TextItem) {
+    let right = (current.x + current.width).max(item.x + item.width);
+    current.x = current.x.min(item.x);
+    current.width = right - current.x;
+    current.height = current.height.max(item.height);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::GlobalStats;
+
+    fn item(text: &str, x: f64, width: f64) -> TextItem {
+        TextItem {
+            text: text.to_string(),
+            x,
+            y: 700.0,
+            width,
+            height: 10.0,
+            font: "F1".to_string(),
+            font_size: 10.0,
+            ..Default::default()
+        }
+    }
+
+    fn merged(items: Vec<TextItem>) -> String {
+        let line = create_line_item(items, &GlobalStats::default()).unwrap();
+        line.items.iter().map(|item| item.text.as_str()).collect()
+    }
+
+    fn glyph(text: &str, x: f64, y: f64) -> TextItem {
+        TextItem {
+            vertical: true,
+            y,
+            ..item(text, x, 10.0)
+        }
+    }
+
+    #[test]
+    fn reads_vertical_columns_right_to_left() {
+        // Left column first in content order, glyphs out of order within the columns
+        let items = vec![
+            glyph("で", 100.0, 688.0),
+            glyph("す", 100.0, 676.0),
+            glyph("字", 114.0, 688.0),
+            glyph("漢", 114.0, 700.0),
+            glyph("は", 114.0, 676.0),
+        ];
+        let lines = vertical_lines(items, &GlobalStats::default());
+        let texts: Vec<String> = lines
+            .iter()
+            .map(|line| line.items.iter().map(|item| item.text.as_str()).collect())
+            .collect();
+        assert_eq!(texts, ["漢字は", "です"]);
+        // Line boxes are back on the page: a narrow, tall column
+        assert!((lines[0].x - 114.0).abs() < 1e-6);
+        assert!((lines[0].y - 700.0).abs() < 1e-6);
+        assert!(lines[0].height > lines[0].width);
+    }
+
+    #[test]
+    fn merges_cjk_runs_without_spaces() {
+        assert_eq!(
+            merged(vec![item("東京都", 0.0, 30.0), item("の人口", 38.0, 30.0)]),
+            "東京都の人口"
+        );
+    }
+
+    #[test]
+    fn keeps_spaces_around_latin_words_in_cjk_text() {
+        assert_eq!(
+            merged(vec![
+                item("The word", 0.0, 40.0),
+                item("漢字", 48.0, 20.0),
+                item("means", 76.0, 25.0),
+            ]),
+            "The word 漢字 means"
+        );
+        assert_eq!(
+            merged(vec![item("最新の", 0.0, 30.0), item("Windows", 38.0, 35.0)]),
+            "最新の Windows"
+        );
+    }
+
+    fn ordered(items: Vec<TextItem>) -> Vec<String> {
+        let mut items = items;
+        order_line(&mut items);
+        items.into_iter().map(|item| item.text).collect()
+    }
+
+    #[test]
+    fn reads_right_to_left_lines_from_the_right() {
+        // Shown as "2024 Windows שלום עולם": the Hebrew reads first, the embedded run keeps
+        // its left-to-right order
+        let words = ordered(vec![
+            item("Windows", 40.0, 35.0),
+            item("עולם", 150.0, 20.0),
+            item("2024", 0.0, 20.0),
+            item("שלום", 100.0, 20.0),
+        ]);
+        assert_eq!(words, ["עולם", "שלום", "2024", "Windows"]);
+    }
+
+    #[test]
+    fn reverses_embedded_right_to_left_runs() {
+        // Shown as "Please see also عليكم سلام"
+        let words = ordered(vec![
+            item("Please", 0.0, 30.0),
+            item("see", 40.0, 20.0),
+            item("also", 70.0, 20.0),
+            item("عليكم", 100.0, 25.0),
+            item("سلام", 140.0, 20.0),
+        ]);
+        assert_eq!(words, ["Please", "see", "also", "سلام", "عليكم"]);
+    }
+}
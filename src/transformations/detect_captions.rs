@@ -0,0 +1,139 @@
+use crate::models::{BlockType, BoundingBox, ItemType, LineItem, ParseResult, WordFormat};
+use crate::transformations::common::{caption_label, join_line_items, Transformation};
+
+/// Marks figure and table captions ("Figure 3-2. Request flow", "Table 4: Limits") as
+/// `BlockType::Caption`. A line needs the caption pattern plus one more hint: an image right
+/// above or below it, punctuation after the number, a formatted label, a smaller font than the
+/// body, or blank space around it. Captions next to an image record that image's region.
+pub struct DetectCaptions {
+    pub verbose: bool,
+}
+
+impl Transformation for DetectCaptions {
+    fn transform(&self, result: &mut ParseResult) {
+        let body_height = result.globals.most_used_height;
+        let body_font = result.globals.most_used_font.clone();
+        let paragraph_gap = result.globals.most_used_distance * 1.1;
+        let mut detected = 0;
+
+        for page in &mut result.pages {
+            let images = &page.images;
+            let ys: Vec<Option<f64>> = page
+                .items
+                .iter()
+                .map(|item| match item {
+                    ItemType::LineItem(line) => Some(line.y),
+                    _ => None,
+                })
+                .collect();
+
+            let mut caption_font: Option<(String, f64)> = None;
+            let mut last_y: Option<f64> = None;
+
+            for (idx, item) in page.items.iter_mut().enumerate() {
+                let ItemType::LineItem(line) = item else {
+                    continue;
+                };
+                let gap_above = last_y.map(|y| (y - line.y).abs());
+                last_y = Some(line.y);
+
+                // Continuation of a multi-line caption set in its own font
+                if let Some((font, size)) = &caption_font {
+                    let same_style = line.items.iter().all(|i| {
+                        i.text.trim().is_empty()
+                            || (i.font == *font && (i.font_size - size).abs() < 0.5)
+                    });
+                    if line.block_type == BlockType::Paragraph
+                        && same_style
+                        && gap_above.is_some_and(|gap| gap <= paragraph_gap)
+                    {
+                        line.block_type = BlockType::Caption;
+                        continue;
+                    }
+                }
+                caption_font = None;
+
+                if !matches!(line.block_type, BlockType::Paragraph | BlockType::H6) {
+                    continue;
+                }
+                let text = join_line_items(line);
+                if caption_label(&text).is_none() {
+                    continue;
+                }
+
+                let gap_below = ys[idx + 1..]
+                    .iter()
+                    .flatten()
+                    .next()
+                    .map(|y| (line.y - y).abs());
+                let isolated = gap_above.is_none_or(|gap| gap > paragraph_gap)
+                    && gap_below.is_none_or(|gap| gap > paragraph_gap);
+
+                let figure = adjacent_image(line, images, paragraph_gap);
+
+                if figure.is_some()
+                    || has_punctuated_label(&text)
+                    || has_formatted_label(line)
+                    || is_smaller(line, body_height)
+                    || isolated
+                {
+                    line.block_type = BlockType::Caption;
+                    line.figure = figure;
+                    detected += 1;
+
+                    // Caption text (after the label) in a non-body font may wrap onto more lines
+                    caption_font = line
+                        .items
+                        .last()
+                        .filter(|i| i.font != body_font || i.font_size < body_height - 0.5)
+                        .map(|i| (i.font.clone(), i.font_size));
+                }
+            }
+        }
+
+        if self.verbose {
+            crate::lgger!("DetectCaptions: Found {} captions", detected);
+        }
+    }
+}
+
+/// Image directly above or below the line (within a few line distances) that overlaps it
+/// horizontally.
+fn adjacent_image(
+    line: &LineItem,
+    images: &[BoundingBox],
+    paragraph_gap: f64,
+) -> Option<BoundingBox> {
+    let reach = paragraph_gap * 3.0;
+    images
+        .iter()
+        .filter(|image| line.x < image.x + image.width && image.x < line.x + line.width)
+        .filter_map(|image| {
+            // Caption below the image, or above it
+            let below = image.y - image.height - line.y;
+            let above = line.y - line.height - image.y;
+            let gap = if below >= 0.0 { below } else { above };
+            (0.0..=reach).contains(&gap).then_some((gap, *image))
+        })
+        .min_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, image)| image)
+}
+
+/// "Figure 3-2." / "Table 4:" rather than "Figure 3 shows".
+fn has_punctuated_label(text: &str) -> bool {
+    text.split_whitespace()
+        .nth(1)
+        .is_some_and(|number| number.ends_with(['.', ':', '\u{2014}', '\u{2013}']))
+}
+
+/// Label set in bold or italic, as most typesetting styles do.
+fn has_formatted_label(line: &LineItem) -> bool {
+    line.items
+        .first()
+        .is_some_and(|item| item.format.is_some_and(|f| f != WordFormat::Code))
+}
+
+fn is_smaller(line: &LineItem, body_height: f64) -> bool {
+    let size = line.items.iter().map(|i| i.font_size).fold(0.0, f64::max);
+    size > 0.0 && size < body_height - 0.5
+}
@@ -11,7 +11,7 @@ impl Transformation for DetectCodeBlocks {
         let _most_used_distance = globals.most_used_distance;
         let total_pages = result.pages.len();
 
-        for (_i, page) in result.pages.iter_mut().enumerate() {
+        for page in result.pages.iter_mut() {
             if self.verbose {
                 crate::lgger!("DetectCodeBlocks: Analyzing {} pages...", total_pages);
             }
@@ -81,8 +81,8 @@ impl Transformation for DetectCodeBlocks {
 
                     let is_indented = line.x > indent_threshold;
                     let is_plain = line.items.iter().all(|i| i.format.is_none());
-                    let has_markdown_bold =
-                        text.trim().starts_with("**") && text.trim().ends_with("**");
+                    let has_markdown_bold = is_formatted(line.items.first(), true)
+                        && is_formatted(line.items.last(), true);
 
                     let l_lower = text.to_lowercase();
                     let has_indicators = l_has_explicit_code_indicators(&text, &l_lower);
@@ -182,8 +182,10 @@ impl Transformation for DetectCodeBlocks {
                                 i.format,
                                 Some(WordFormat::Italic) | Some(WordFormat::BoldItalic)
                             )
-                        }) || (text.trim().starts_with('_') && text.trim().ends_with('_'))
-                            || (text.trim().starts_with('*') && text.trim().ends_with('*')));
+                        }) || (is_formatted(line.items.first(), false)
+                            && is_formatted(line.items.last(), false))
+                            || (is_formatted(line.items.first(), true)
+                                && is_formatted(line.items.last(), true)));
 
                     if is_italic {
                         current_italic_group.push(idx);
@@ -304,3 +306,13 @@ fn l_has_explicit_code_indicators(text: &str, lower: &str) -> bool {
         || text.contains(" = ")
         || text.contains("):")
 }
+
+/// Whether `item` carries bold (`bold == true`) or italic formatting, including bold italic.
+fn is_formatted(item: Option<&crate::models::TextItem>, bold: bool) -> bool {
+    item.is_some_and(|i| match i.format {
+        Some(WordFormat::BoldItalic) => true,
+        Some(WordFormat::Bold) => bold,
+        Some(WordFormat::Italic) => !bold,
+        _ => false,
+    })
+}
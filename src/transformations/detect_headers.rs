@@ -1,5 +1,6 @@
-use crate::models::{BlockType, ParseResult};
-use crate::transformations::common::Transformation;
+use crate::models::{BlockType, ItemType, LineItem, ParseResult, WordFormat};
+use crate::transformations::common::{join_line_items, section_depth, Transformation};
+use std::collections::HashMap;
 
 pub struct DetectHeaders {
     pub verbose: bool,
@@ -48,7 +49,7 @@ impl Transformation for DetectHeaders {
                     // Simple list item check (start with - or * or number.)
                     let is_list_item = text.trim().starts_with('-')
                         || text.trim().starts_with('*')
-                        || (text.trim().chars().next().map_or(false, |c| c.is_numeric())
+                        || (text.trim().chars().next().is_some_and(|c| c.is_numeric())
                             && text.trim().contains('.'));
 
                     let h = line.items.iter().map(|i| i.font_size).fold(0.0, f64::max);
@@ -62,10 +63,11 @@ impl Transformation for DetectHeaders {
                         );
                     }
 
-                    if h > min_header_height && !is_list_item {
-                        if !distinct_heights.iter().any(|&dh| (dh - h).abs() < 1.0) {
-                            distinct_heights.push(h);
-                        }
+                    if h > min_header_height
+                        && !is_list_item
+                        && !distinct_heights.iter().any(|&dh| (dh - h).abs() < 1.0)
+                    {
+                        distinct_heights.push(h);
                     }
                 }
             }
@@ -158,35 +160,21 @@ impl Transformation for DetectHeaders {
                             .collect::<Vec<_>>()
                             .join("");
 
-                        // Check for **Wrapped Header**
+                        // Check for a bold-wrapped header (first and last words bold)
+                        let is_bold = |item: Option<&crate::models::TextItem>| {
+                            item.is_some_and(|i| {
+                                matches!(
+                                    i.format,
+                                    Some(crate::models::WordFormat::Bold)
+                                        | Some(crate::models::WordFormat::BoldItalic)
+                                )
+                            })
+                        };
                         let is_bold_wrapped =
-                            text.trim().starts_with("**") && text.trim().ends_with("**");
+                            is_bold(line.items.first()) && is_bold(line.items.last());
 
                         if is_bold_wrapped && text.len() < 150 {
-                            // Strip ** from text items
-                            if let Some(first) = line.items.first_mut() {
-                                if first.text.starts_with("**") {
-                                    first.text = first.text.replacen("**", "", 1);
-                                }
-                            }
-                            if let Some(last) = line.items.last_mut() {
-                                if last.text.ends_with("**") {
-                                    // Use range to remove last 2 chars
-                                    let len = last.text.len();
-                                    if len >= 2 {
-                                        last.text.truncate(len - 2);
-                                    }
-                                }
-                            }
-
-                            // Re-evaluate text
-                            let clean_text = line
-                                .items
-                                .iter()
-                                .map(|i| i.text.as_str())
-                                .collect::<Vec<_>>()
-                                .join("");
-                            if clean_text.trim().is_empty() {
+                            if text.trim().is_empty() {
                                 continue; // Don't make empty header
                             }
 
@@ -219,10 +207,10 @@ impl Transformation for DetectHeaders {
                                 .unwrap_or(0);
                             let mut isolated_top = true;
 
-                            if line_pos > 0 {
-                                if (line_ys[line_pos - 1] - y).abs() < most_used_dist * 1.5 {
-                                    isolated_top = false;
-                                }
+                            if line_pos > 0
+                                && (line_ys[line_pos - 1] - y).abs() < most_used_dist * 1.5
+                            {
+                                isolated_top = false;
                             }
 
                             if isolated_top {
@@ -251,15 +239,14 @@ impl Transformation for DetectHeaders {
                             .position(|&ly| (ly - y).abs() < 0.1)
                             .unwrap_or(0);
 
-                        if line_pos > 0 {
-                            if (line_ys[line_pos - 1] - y).abs() < most_used_dist * 1.5 {
-                                isolated = false;
-                            }
+                        if line_pos > 0 && (line_ys[line_pos - 1] - y).abs() < most_used_dist * 1.5
+                        {
+                            isolated = false;
                         }
-                        if line_pos < line_ys.len() - 1 {
-                            if (y - line_ys[line_pos + 1]).abs() < most_used_dist * 1.5 {
-                                isolated = false;
-                            }
+                        if line_pos < line_ys.len() - 1
+                            && (y - line_ys[line_pos + 1]).abs() < most_used_dist * 1.5
+                        {
+                            isolated = false;
                         }
 
                         let letter_count = text.chars().filter(|c| c.is_alphabetic()).count();
@@ -278,8 +265,115 @@ impl Transformation for DetectHeaders {
             }
         }
 
+        // 5. Section Numbering Logic
+        detected_headers += apply_section_numbering(result, self.verbose);
+
         if self.verbose {
             crate::lgger!("DetectHeaders: Found {} headers", detected_headers);
         }
     }
 }
+
+/// Sets the level of numbered headings ("2.3.1 Retry budgets", "Chapter 4", "Part II") from
+/// their numbering depth. The font-size levels decide where depth 1 lands: the most common
+/// difference between font level and depth among numbered headings becomes the offset.
+/// Numbered body lines styled apart from the text are promoted too. Returns how many lines
+/// became headings.
+fn apply_section_numbering(result: &mut ParseResult, verbose: bool) -> usize {
+    let most_used_height = result.globals.most_used_height;
+    let most_used_font = result.globals.most_used_font.clone();
+
+    let mut offset_votes: HashMap<i64, usize> = HashMap::new();
+    let mut min_depth = usize::MAX;
+    let mut has_title = false;
+    for page in &result.pages {
+        for item in &page.items {
+            let ItemType::LineItem(line) = item else {
+                continue;
+            };
+            let Some(level) = line.block_type.heading_level() else {
+                continue;
+            };
+            match section_depth(&join_line_items(line)) {
+                Some(depth) => {
+                    *offset_votes.entry(level as i64 - depth as i64).or_insert(0) += 1;
+                    min_depth = min_depth.min(depth);
+                }
+                None => has_title |= level == 1,
+            }
+        }
+    }
+
+    // Without numbered headings to learn from, depth 1 sits below a document title if any
+    let default_offset = i64::from(has_title);
+    let offset = offset_votes
+        .into_iter()
+        .max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(&a.0)))
+        .map_or(default_offset, |(offset, _)| offset);
+    // Parts (depth 0) or a deep offset must still leave the top number at H1 or below
+    let offset = if min_depth == usize::MAX {
+        offset
+    } else {
+        offset.max(1 - min_depth as i64)
+    };
+    if verbose {
+        crate::lgger!("DetectHeaders: section numbering offset={}", offset);
+    }
+
+    let mut promoted = 0;
+    for page in &mut result.pages {
+        for item in &mut page.items {
+            let ItemType::LineItem(line) = item else {
+                continue;
+            };
+            let is_heading = line.block_type.heading_level().is_some();
+            if !is_heading && line.block_type != BlockType::Paragraph {
+                continue;
+            }
+            let text = join_line_items(line);
+            let Some(depth) = section_depth(&text) else {
+                continue;
+            };
+            if !is_heading {
+                // "1. Install" is a numbered list; only labelled or multi-level numbers qualify
+                let labelled = !text.starts_with(|c: char| c.is_ascii_digit());
+                if (depth < 2 && !labelled)
+                    || !looks_like_heading(line, &text, most_used_height, &most_used_font)
+                {
+                    continue;
+                }
+                promoted += 1;
+            }
+            let level = (depth as i64 + offset).clamp(1, 6) as usize;
+            line.block_type = BlockType::heading(level);
+        }
+    }
+    promoted
+}
+
+/// Short line without sentence punctuation, set apart from body text by size, font or weight.
+fn looks_like_heading(
+    line: &LineItem,
+    text: &str,
+    most_used_height: f64,
+    most_used_font: &str,
+) -> bool {
+    let trimmed = text.trim_end();
+    if trimmed.len() >= 100 || trimmed.ends_with(['.', ',', ';', ':']) {
+        return false;
+    }
+    let words: Vec<_> = line
+        .items
+        .iter()
+        .filter(|i| !i.text.trim().is_empty())
+        .collect();
+    let larger = words.iter().any(|i| i.font_size > most_used_height + 0.5);
+    let other_font = words.iter().all(|i| i.font != most_used_font);
+    let bold = words.iter().all(|i| {
+        matches!(
+            i.format,
+            Some(WordFormat::Bold) | Some(WordFormat::BoldItalic)
+        )
+    });
+    !words.is_empty() && (larger || other_font || bold)
+}
@@ -0,0 +1,182 @@
+use crate::models::{BlockType, BoundingBox, ItemType, LineItem, ParseResult};
+use crate::transformations::common::{join_line_items, list_marker, Transformation};
+
+/// Marks paragraph lines that open with a bullet or an item number ("•", "-", "2.", "(2)") as
+/// `BlockType::ListItem`. Lines that wrap an item continue it when they follow within normal
+/// line spacing and are indented past the marker; they are merged into the item's line, so
+/// each `ListItem` line is one whole item.
+pub struct DetectLists {
+    pub verbose: bool,
+}
+
+impl Transformation for DetectLists {
+    fn transform(&self, result: &mut ParseResult) {
+        let line_distance = result.globals.most_used_distance;
+        let mut detected = 0;
+        let mut wrapped = 0;
+
+        for page in &mut result.pages {
+            let mut items: Vec<ItemType> = Vec::with_capacity(page.items.len());
+            for item in page.items.drain(..) {
+                let ItemType::LineItem(mut line) = item else {
+                    items.push(item);
+                    continue;
+                };
+                if line.block_type != BlockType::Paragraph {
+                    items.push(ItemType::LineItem(line));
+                    continue;
+                }
+
+                if list_marker(&join_line_items(&line)).is_some() {
+                    line.block_type = BlockType::ListItem;
+                    detected += 1;
+                } else if let Some(ItemType::LineItem(prev)) = items.last_mut() {
+                    if prev.block_type == BlockType::ListItem
+                        && continues_item(prev, &line, line_distance)
+                    {
+                        append_wrapped_line(prev, line);
+                        wrapped += 1;
+                        continue;
+                    }
+                }
+                items.push(ItemType::LineItem(line));
+            }
+            page.items = items;
+        }
+
+        if self.verbose {
+            crate::lgger!(
+                "DetectLists: {} list items, {} wrapped lines merged",
+                detected,
+                wrapped
+            );
+        }
+    }
+}
+
+/// A wrapped line of a list item sits right below the item and starts to the right of its
+/// marker (a hanging indent); an unindented line is a paragraph after the list.
+fn continues_item(item: &LineItem, line: &LineItem, line_distance: f64) -> bool {
+    let font_size = line.items.first().map_or(0.0, |first| first.font_size);
+    let distance = if line_distance > 0.0 {
+        line_distance
+    } else {
+        font_size * 1.2
+    };
+    let last_y = item.y - item.height + line.height;
+    let gap = last_y - line.y;
+    gap > 0.0 && gap <= distance * 1.1 && line.x > item.x + font_size * 0.5
+}
+
+/// Appends a wrapped line to its list item. A hyphen at the end of the item is dropped and
+/// the word glued back together; otherwise the lines are joined with a space.
+fn append_wrapped_line(item: &mut LineItem, line: LineItem) {
+    let bbox = BoundingBox::of_line(item).union(&BoundingBox::of_line(&line));
+    let hyphenated = item.items.last_mut().is_some_and(|last| {
+        let trimmed_len = last.text.trim_end().len();
+        let hyphenated = last.text[..trimmed_len].ends_with('-');
+        if hyphenated {
+            last.text.truncate(trimmed_len - 1);
+        }
+        hyphenated
+    });
+
+    let mut wrapped = line.items;
+    if hyphenated && !wrapped.is_empty() {
+        let first = wrapped.remove(0);
+        if let Some(last) = item.items.last_mut() {
+            last.text.push_str(first.text.trim_start());
+        }
+    } else if let Some(first) = wrapped.first_mut() {
+        if !first.text.starts_with(' ') {
+            first.text.insert(0, ' ');
+        }
+    }
+    item.items.extend(wrapped);
+
+    item.x = bbox.x;
+    item.y = bbox.y;
+    item.width = bbox.width;
+    item.height = bbox.height;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{GlobalStats, Page, TextItem};
+
+    fn line(text: &str, x: f64, y: f64) -> ItemType {
+        ItemType::LineItem(LineItem {
+            items: vec![TextItem {
+                text: text.to_string(),
+                x,
+                y,
+                width: 100.0,
+                height: 10.0,
+                font_size: 10.0,
+                ..Default::default()
+            }],
+            x,
+            y,
+            width: 100.0,
+            height: 10.0,
+            ..Default::default()
+        })
+    }
+
+    fn detect(items: Vec<ItemType>) -> Vec<(BlockType, String)> {
+        let mut result = ParseResult {
+            pages: vec![Page {
+                items,
+                ..Default::default()
+            }],
+            globals: GlobalStats {
+                most_used_distance: 12.0,
+                ..Default::default()
+            },
+        };
+        DetectLists { verbose: false }.transform(&mut result);
+        result.pages[0]
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                ItemType::LineItem(line) => Some((line.block_type, join_line_items(line))),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn merges_wrapped_lines_into_their_items() {
+        let lines = detect(vec![
+            line("Steps:", 50.0, 700.0),
+            line("1. Download the pack-", 50.0, 688.0),
+            line("age and unpack it", 62.0, 676.0),
+            line("2. Run the installer", 50.0, 664.0),
+            line("After that, restart.", 50.0, 652.0),
+        ]);
+        assert_eq!(
+            lines,
+            [
+                (BlockType::Paragraph, "Steps:".to_string()),
+                (
+                    BlockType::ListItem,
+                    "1. Download the package and unpack it".to_string()
+                ),
+                (BlockType::ListItem, "2. Run the installer".to_string()),
+                (BlockType::Paragraph, "After that, restart.".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn leaves_other_blocks_alone() {
+        let mut heading = line("1. Introduction", 50.0, 700.0);
+        if let ItemType::LineItem(line) = &mut heading {
+            line.block_type = BlockType::H2;
+        }
+        let lines = detect(vec![heading, line("- not a list", 80.0, 640.0)]);
+        assert_eq!(lines[0].0, BlockType::H2);
+        assert_eq!(lines[1].0, BlockType::ListItem);
+    }
+}
@@ -0,0 +1,73 @@
+use crate::models::{ItemType, ParseResult, RenderMode, TextItem};
+use crate::transformations::common::Transformation;
+
+/// What to do with the text of pages that carry an OCR layer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum OcrPolicy {
+    /// Convert OCR text like any other text
+    #[default]
+    Use,
+    /// Drop the OCR text layer
+    Skip,
+    /// Keep OCR text but flag it as low confidence in the output
+    LowConfidence,
+}
+
+/// Flags pages whose text comes from an OCR layer over a scan: mostly invisible text (render
+/// mode 3) or text in a placeholder font such as GlyphLessFont, as well as scanned pages
+/// recognized during extraction. Statistics skip these pages when the document also has
+/// born-digital pages, and `policy` decides what happens to their text.
+pub struct DetectOcrPages {
+    pub verbose: bool,
+    pub policy: OcrPolicy,
+}
+
+impl Transformation for DetectOcrPages {
+    fn transform(&self, result: &mut ParseResult) {
+        let mut detected = Vec::new();
+
+        for page in &mut result.pages {
+            // Scanned pages recognized during extraction hold nothing but OCR text
+            let recognized = page.ocr;
+            let mut total = 0;
+            let mut ocr = 0;
+            for item in &page.items {
+                if let ItemType::TextItem(text_item) = item {
+                    let chars = text_item.text.trim().chars().count();
+                    total += chars;
+                    if is_ocr_text(text_item) {
+                        ocr += chars;
+                    }
+                }
+            }
+            // Born-digital pages may hide a few invisible words; an OCR layer is most of the page
+            if !recognized && (total == 0 || (ocr as f64) < total as f64 * 0.8) {
+                continue;
+            }
+
+            page.ocr = true;
+            detected.push(page.index + 1);
+            match self.policy {
+                OcrPolicy::Use => {}
+                OcrPolicy::Skip => page.items.retain(|item| match item {
+                    ItemType::TextItem(text_item) => !recognized && !is_ocr_text(text_item),
+                    _ => true,
+                }),
+                OcrPolicy::LowConfidence => page.low_confidence = true,
+            }
+        }
+
+        if self.verbose {
+            crate::lgger!(
+                "DetectOcrPages: {} OCR pages {:?} ({:?})",
+                detected.len(),
+                detected,
+                self.policy
+            );
+        }
+    }
+}
+
+fn is_ocr_text(item: &TextItem) -> bool {
+    item.render_mode == RenderMode::Invisible || item.font.to_lowercase().contains("glyphless")
+}
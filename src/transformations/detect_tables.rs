@@ -0,0 +1,285 @@
+use crate::models::{BlockType, ItemType, LineItem, ParseResult, TextItem};
+use crate::transformations::common::{horizontal_gap, join_line_items, Transformation};
+
+/// Marks runs of lines laid out in columns as `BlockType::TableRow`. A row is a paragraph line
+/// whose text falls into two or more cells separated by wide gaps; consecutive rows form a
+/// table when their cells line up into the same columns. Each row records its cell texts, one
+/// per column, so renderers can rebuild the grid.
+pub struct DetectTables {
+    pub verbose: bool,
+}
+
+/// Horizontal span of one cell and its text.
+#[derive(Debug, Clone)]
+struct Cell {
+    left: f64,
+    right: f64,
+    text: String,
+}
+
+impl Transformation for DetectTables {
+    fn transform(&self, result: &mut ParseResult) {
+        let line_distance = result.globals.most_used_distance;
+        let mut tables = 0;
+        let mut rows = 0;
+
+        for page in &mut result.pages {
+            let mut run: Vec<(usize, Vec<Cell>)> = Vec::new();
+            let mut columns: Vec<(f64, f64)> = Vec::new();
+            let mut last_y: Option<f64> = None;
+
+            for idx in 0..page.items.len() {
+                let ItemType::LineItem(line) = &page.items[idx] else {
+                    continue;
+                };
+                let cells = row_cells(line);
+                let close = last_y.is_some_and(|y| {
+                    let gap = y - line.y;
+                    gap > 0.0 && gap <= row_distance(line, line_distance)
+                });
+                last_y = Some(line.y);
+
+                if cells.len() >= 2 && close {
+                    if let Some(merged) = add_row(&columns, &run, &cells) {
+                        columns = merged;
+                        run.push((idx, cells));
+                        continue;
+                    }
+                }
+
+                if mark_table(&mut page.items, &run, &columns) {
+                    tables += 1;
+                    rows += run.len();
+                }
+                run.clear();
+                columns.clear();
+                if cells.len() >= 2 {
+                    columns = merge_columns(Vec::new(), &cells);
+                    run.push((idx, cells));
+                }
+            }
+
+            if mark_table(&mut page.items, &run, &columns) {
+                tables += 1;
+                rows += run.len();
+            }
+        }
+
+        if self.verbose {
+            crate::lgger!("DetectTables: {} tables with {} rows", tables, rows);
+        }
+    }
+}
+
+/// Splits a paragraph line into cells at gaps wider than one and a half character heights.
+/// Other block types and right-to-left lines yield no cells.
+fn row_cells(line: &LineItem) -> Vec<Cell> {
+    if line.block_type != BlockType::Paragraph || line.rtl {
+        return Vec::new();
+    }
+
+    let mut groups: Vec<Vec<TextItem>> = Vec::new();
+    for item in line
+        .items
+        .iter()
+        .filter(|item| !item.text.trim().is_empty())
+    {
+        match groups.last_mut() {
+            Some(group)
+                if group.last().is_some_and(|prev| {
+                    horizontal_gap(prev, item) <= item.font_size.max(1.0) * 1.5
+                }) =>
+            {
+                group.push(item.clone())
+            }
+            _ => groups.push(vec![item.clone()]),
+        }
+    }
+
+    groups
+        .into_iter()
+        .map(|items| {
+            let left = items.iter().map(|i| i.x).fold(f64::MAX, f64::min);
+            let right = items.iter().map(|i| i.x + i.width).fold(f64::MIN, f64::max);
+            let cell = LineItem {
+                items,
+                ..Default::default()
+            };
+            Cell {
+                left,
+                right,
+                text: join_line_items(&cell)
+                    .split_whitespace()
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            }
+        })
+        .collect()
+}
+
+/// Rows of a table may sit further apart than lines of a paragraph, but not by more than a
+/// couple of blank lines.
+fn row_distance(line: &LineItem, line_distance: f64) -> f64 {
+    let font_size = line.items.first().map_or(0.0, |first| first.font_size);
+    let distance = if line_distance > 0.0 {
+        line_distance
+    } else {
+        font_size * 1.2
+    };
+    distance * 2.5
+}
+
+/// Columns of the table with `cells` added as a row, or `None` if the row does not fit: two of
+/// its cells would fall into one column, or it would merge columns that earlier rows keep apart.
+fn add_row(
+    columns: &[(f64, f64)],
+    run: &[(usize, Vec<Cell>)],
+    cells: &[Cell],
+) -> Option<Vec<(f64, f64)>> {
+    let merged = merge_columns(columns.to_vec(), cells);
+    let fits = |cells: &[Cell]| {
+        let mut used = vec![false; merged.len()];
+        cells.iter().all(|cell| {
+            let column = column_of(&merged, cell);
+            !std::mem::replace(&mut used[column], true)
+        })
+    };
+    (fits(cells) && run.iter().all(|(_, cells)| fits(cells))).then_some(merged)
+}
+
+/// Unions the horizontal spans of `cells` into `columns`, joining spans that overlap.
+fn merge_columns(mut columns: Vec<(f64, f64)>, cells: &[Cell]) -> Vec<(f64, f64)> {
+    columns.extend(cells.iter().map(|cell| (cell.left, cell.right)));
+    columns.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut merged: Vec<(f64, f64)> = Vec::new();
+    for (left, right) in columns {
+        match merged.last_mut() {
+            Some(last) if left <= last.1 => last.1 = last.1.max(right),
+            _ => merged.push((left, right)),
+        }
+    }
+    merged
+}
+
+fn column_of(columns: &[(f64, f64)], cell: &Cell) -> usize {
+    columns
+        .iter()
+        .position(|&(left, right)| cell.left <= right && cell.right >= left)
+        .unwrap_or(0)
+}
+
+/// Marks the rows of `run` as a table if it is one: at least two rows, and cells short enough
+/// that the run is not two columns of running text set side by side.
+fn mark_table(items: &mut [ItemType], run: &[(usize, Vec<Cell>)], columns: &[(f64, f64)]) -> bool {
+    if run.len() < 2 {
+        return false;
+    }
+    let cells: Vec<&Cell> = run.iter().flat_map(|(_, cells)| cells).collect();
+    let prose = cells
+        .iter()
+        .filter(|cell| cell.text.split_whitespace().count() > 8)
+        .count();
+    if prose * 2 > cells.len() {
+        return false;
+    }
+
+    for (idx, cells) in run {
+        if let ItemType::LineItem(line) = &mut items[*idx] {
+            let mut row = vec![String::new(); columns.len()];
+            for cell in cells {
+                row[column_of(columns, cell)] = cell.text.clone();
+            }
+            line.block_type = BlockType::TableRow;
+            line.cells = row;
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{GlobalStats, Page};
+
+    fn row(cells: &[(&str, f64)], y: f64) -> ItemType {
+        ItemType::LineItem(LineItem {
+            items: cells
+                .iter()
+                .map(|&(text, x)| TextItem {
+                    text: text.to_string(),
+                    x,
+                    y,
+                    width: text.len() as f64 * 5.0,
+                    height: 10.0,
+                    font_size: 10.0,
+                    ..Default::default()
+                })
+                .collect(),
+            x: cells[0].1,
+            y,
+            height: 10.0,
+            ..Default::default()
+        })
+    }
+
+    fn detect(items: Vec<ItemType>) -> Vec<(BlockType, Vec<String>)> {
+        let mut result = ParseResult {
+            pages: vec![Page {
+                items,
+                ..Default::default()
+            }],
+            globals: GlobalStats {
+                most_used_distance: 12.0,
+                ..Default::default()
+            },
+        };
+        DetectTables { verbose: false }.transform(&mut result);
+        result.pages[0]
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                ItemType::LineItem(line) => Some((line.block_type, line.cells.clone())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn aligns_cells_into_columns() {
+        let lines = detect(vec![
+            row(&[("Limits are listed below.", 50.0)], 700.0),
+            row(&[("Name", 50.0), ("Size", 200.0), ("Unit", 300.0)], 680.0),
+            row(&[("Width", 50.0), ("12", 210.0), ("mm", 300.0)], 666.0),
+            // No value in the middle column
+            row(&[("Depth", 50.0), ("in", 300.0)], 652.0),
+            row(&[("Weight is not limited.", 50.0)], 630.0),
+        ]);
+        let cells = |row: &[&str]| row.iter().map(|c| c.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            [
+                (BlockType::Paragraph, vec![]),
+                (BlockType::TableRow, cells(&["Name", "Size", "Unit"])),
+                (BlockType::TableRow, cells(&["Width", "12", "mm"])),
+                (BlockType::TableRow, cells(&["Depth", "", "in"])),
+                (BlockType::Paragraph, vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn needs_two_rows_of_short_cells() {
+        // A lone line with a wide gap is not a table
+        let lines = detect(vec![row(&[("Chapter 1", 50.0), ("Intro", 200.0)], 700.0)]);
+        assert_eq!(lines[0].0, BlockType::Paragraph);
+
+        // Two columns of running text side by side are not one either
+        let left = "the quick brown fox jumps over the lazy dog again";
+        let lines = detect(vec![
+            row(&[(left, 50.0), (left, 320.0)], 700.0),
+            row(&[(left, 50.0), (left, 320.0)], 688.0),
+        ]);
+        assert!(lines.iter().all(|(kind, _)| *kind == BlockType::Paragraph));
+    }
+}
@@ -1,15 +1,36 @@
-use crate::models::{BlockType, ItemType, LineItem, ParseResult, TextItem};
-use crate::transformations::common::Transformation;
+use crate::models::{
+    BlockType, GlobalStats, ItemType, LineItem, Page, ParseResult, TextItem, TocList, TocTarget,
+};
+use crate::transformations::common::{caption_label, is_roman, join_line_items, Transformation};
 use std::collections::{HashMap, HashSet};
 
 pub struct DetectTOC {
     pub verbose: bool,
+    /// Leading pages in which any TOC-like page is accepted (20 if unset). TOCs further into
+    /// the document are only accepted if they open with a title such as "Contents".
+    pub scan_pages: Option<usize>,
 }
 
+const DEFAULT_SCAN_PAGES: usize = 20;
+
+/// Result of analysing one page as a TOC page.
+struct PageScan {
+    /// Page items with detached page numbers moved back onto their lines
+    items: Vec<ItemType>,
+    toc_links: Vec<TocLink>,
+    unknown_lines: HashSet<usize>,
+    /// The page opens with a TOC-like title ("Contents", "List of Figures", ...)
+    has_title: bool,
+}
+
+#[derive(Clone)]
 struct TocLink {
     line_item: LineItem,
     level: usize,
     original_line_index: usize,
+    /// Page number printed at the end of the entry
+    page_label: String,
+    list: TocList,
 }
 
 struct LinkLeveler {
@@ -122,246 +143,70 @@ impl LinkLeveler {
 
 impl Transformation for DetectTOC {
     fn transform(&self, result: &mut ParseResult) {
-        let max_pages_to_evaluate = std::cmp::min(20, result.pages.len());
-        let mut link_leveler = LinkLeveler::new();
-        // toc_pages is unused
-
-        // We need to modify pages in place, but we need to iterate first to identify TOC pages.
-        // Rust borrowing rules make this tricky.
-        // We can collect indices of TOC pages first.
-
-        let mut toc_page_indices = Vec::new();
-        let mut toc_links_by_page: HashMap<usize, Vec<TocLink>> = HashMap::new();
-        let mut unknown_lines_by_page: HashMap<usize, HashSet<usize>> = HashMap::new(); // page_idx -> set of line indices (original indices)
-
-        for (page_idx, page) in result.pages.iter().enumerate().take(max_pages_to_evaluate) {
-            let mut line_items_with_digits = 0;
-            let mut page_toc_links = Vec::new();
-            let mut unknown_lines = HashSet::new();
-
-            let mut last_words_without_number: Option<Vec<TextItem>> = None;
-            let mut last_line_idx: Option<usize> = None;
-            let mut last_y: Option<f64> = None;
-
-            let mut headline_item_idx: Option<usize> = None;
-
-            let mut processed_items = 0;
-
-            for (line_idx, item) in page.items.iter().enumerate() {
-                if let ItemType::LineItem(line) = item {
-                    processed_items += 1;
-
-                    if page_idx == 6 && self.verbose {
-                        crate::lgger!(
-                            "DEBUG: Page 6 Line raw: {:?}",
-                            line.items.iter().map(|w| &w.text).collect::<Vec<_>>()
-                        );
-                    }
-
-                    // Logic to extract digits at end of line
-                    // Rust equivalent of JS logic:
-                    // words = line.words.filter(...)
-                    // while words.last is number -> pop
-
-                    let mut words: Vec<TextItem> = line.items.clone();
-                    // Filter out words that are only dots
-                    words.retain(|w| !w.text.chars().all(|c| c == '.'));
-
-                    let mut digits = String::new();
-
-                    // Check last word for digits, ignoring trailing markdown/dots
-                    if let Some(last_idx) = words.len().checked_sub(1) {
-                        let last = &mut words[last_idx];
-                        let original_text = last.text.clone();
-
-                        // 1. Remove trailing markdown chars (*, _, space) for check
-                        let trimmed_text =
-                            original_text.trim_end_matches(|c| c == '*' || c == '_' || c == ' ');
-
-                        // 2. Check if it ends with digits
-                        let mut suffix_digits = String::new();
-                        let mut temp_text = trimmed_text.to_string();
-
-                        while let Some(c) = temp_text.chars().last() {
-                            if c.is_digit(10) {
-                                suffix_digits.insert(0, c);
-                                temp_text.pop();
-                            } else {
-                                break;
-                            }
-                        }
-
-                        if !suffix_digits.is_empty() {
-                            digits = suffix_digits;
-
-                            // 3. Update the last word text
-                            // Find last occurrence of digits in original_text
-                            if let Some(pos) = original_text.rfind(&digits) {
-                                // Everything before digits is the candidate for new text
-                                let prefix = &original_text[..pos];
-                                // Everything after digits is the trailing markdown
-                                let suffix = &original_text[pos + digits.len()..];
-
-                                // Clean the prefix (remove trailing dots/spaces)
-                                let clean_prefix =
-                                    prefix.trim_end_matches(|c| c == '.' || c == ' ');
-
-                                // Reconstruct
-                                last.text = format!("{}{}", clean_prefix, suffix);
-                            }
-                        }
-                    }
-
-                    // Remove empty words (e.g. if word was just digits)
-                    words.retain(|w| !w.text.is_empty());
-
-                    let ends_with_digit = !digits.is_empty();
-
-                    if page_idx == 6 && self.verbose {
-                        let text = words
-                            .iter()
-                            .map(|w| w.text.as_str())
-                            .collect::<Vec<_>>()
-                            .join(" ");
-                        crate::lgger!("DEBUG: Page 6 processed words: {:?}, digits: '{}', ends_with_digit: {}", 
-                             words.iter().map(|w| &w.text).collect::<Vec<_>>(),
-                             digits,
-                             ends_with_digit
-                         );
-                        if !digits.is_empty() || text.contains("First Things First") {
-                            crate::lgger!("DEBUG: detailed digit check for '{}':", text);
-                            if let Some(last_idx) = words.len().checked_sub(1) {
-                                let last = &words[last_idx];
-                                let original = &last.text;
-                                let trimmed =
-                                    original.trim_end_matches(|c| c == '*' || c == '_' || c == ' ');
-                                crate::lgger!("  Original: '{}'", original);
-                                crate::lgger!("  Trimmed: '{}'", trimmed);
-                                if let Some(c) = trimmed.chars().last() {
-                                    crate::lgger!(
-                                        "  Last char: '{}', is_digit: {}",
-                                        c,
-                                        c.is_digit(10)
-                                    );
-                                } else {
-                                    crate::lgger!("  Trimmed is empty");
-                                }
-                            }
-                        }
-                    }
-
-                    let ends_with_digit = Self::line_ends_with_digit(line);
-
-                    if ends_with_digit {
-                        if let Some(prev_words) = last_words_without_number.take() {
-                            // Check gap to avoid merging unrelated headers
-                            let threshold = result.globals.most_used_distance * 1.5;
-                            let gap = (last_y.unwrap_or(line.y) - line.y).abs();
-
-                            if page_idx == 6 && self.verbose {
-                                crate::lgger!(
-                                    "DEBUG: Merge check. gap={:.2}, threshold={:.2}",
-                                    gap,
-                                    threshold
-                                );
-                            }
-
-                            if gap > threshold {
-                                // Don't merge. Treat previous as unknown (likely header)
-                                if let Some(idx) = last_line_idx {
-                                    unknown_lines.insert(idx);
-                                }
-                                // Current line is start of new item
-                            } else {
-                                // Merge
-                                let mut new_words = prev_words;
-                                new_words.extend(words);
-                                words = new_words;
-                            }
-                        }
-
-                        // Create a new LineItem for the TOC link
-                        let mut link_line_item = line.clone();
-                        link_line_item.items = words;
-
-                        // Update x/y to match the first item (in case of multiline merge where first line starts earlier)
-                        if let Some(first_item) = link_line_item.items.first() {
-                            link_line_item.x = first_item.x;
-                            link_line_item.y = first_item.y;
-                        }
-
-                        if self.verbose {
-                            let text = link_line_item
-                                .items
-                                .iter()
-                                .map(|w| w.text.as_str())
-                                .collect::<String>();
-                            if text.contains("Learning from Failure")
-                                || text.contains("Talking About Failure")
-                                || text.contains("Postincident Reviews")
-                            {
-                                crate::lgger!(
-                                    "DEBUG: TOC Item '{}' X={:.2}",
-                                    text,
-                                    link_line_item.x
-                                );
-                            }
-                            if link_line_item.x > 77.0 && link_line_item.x < 80.0 {
-                                crate::lgger!(
-                                    "DEBUG: Found item at X={:.2}: '{}'",
-                                    link_line_item.x,
-                                    text
-                                );
-                            }
-                        }
-
-                        page_toc_links.push(TocLink {
-                            line_item: link_line_item,
-                            level: 0, // Will be set by leveler
-                            original_line_index: line_idx,
-                        });
-
-                        line_items_with_digits += 1;
-                    } else {
-                        if headline_item_idx.is_none() {
-                            headline_item_idx = Some(line_idx);
-                        } else {
-                            if let Some(idx) = last_line_idx {
-                                // Previous line was also without number, mark it unknown
-                                unknown_lines.insert(idx);
-                            }
-                            last_words_without_number = Some(words);
-                            last_line_idx = Some(line_idx);
-                            last_y = Some(line.y);
-                        }
-                    }
-                }
+        let scan_pages = self.scan_pages.unwrap_or(DEFAULT_SCAN_PAGES);
+        // Lists of figures and tables are levelled independently from the main TOC
+        let mut link_levelers: HashMap<TocList, LinkLeveler> = HashMap::new();
+
+        // Every page is scanned; the scan window only decides which runs need a TOC title
+        let mut scans: Vec<Option<PageScan>> = result
+            .pages
+            .iter()
+            .map(|page| self.scan_page(page, &result.globals))
+            .collect();
+
+        // Contiguous TOC pages form a run. Runs near the start are accepted as before; later
+        // ones (e.g. a list of figures after the preface) must open with a TOC-like title.
+        let mut runs: Vec<Vec<usize>> = Vec::new();
+        for (page_idx, scan) in scans.iter().enumerate() {
+            if scan.is_none() {
+                continue;
             }
-
-            if processed_items > 0
-                && (line_items_with_digits as f64 * 100.0 / processed_items as f64) > 75.0
-            {
-                toc_page_indices.push(page_idx);
-
-                // Level items
-                link_leveler.level_page_items(&mut page_toc_links);
-
-                toc_links_by_page.insert(page_idx, page_toc_links);
-                unknown_lines_by_page.insert(page_idx, unknown_lines);
-
-                if self.verbose {
-                    crate::lgger!("DEBUG: Detected TOC page {}", page.index);
-                }
+            match runs.last_mut() {
+                Some(run) if run.last() == Some(&(page_idx - 1)) => run.push(page_idx),
+                _ => runs.push(vec![page_idx]),
+            }
+        }
+        runs.retain(|run| {
+            let first = run[0];
+            first < scan_pages || scans[first].as_ref().is_some_and(|scan| scan.has_title)
+        });
+
+        if self.verbose {
+            for run in &runs {
+                crate::lgger!(
+                    "DetectTOC: TOC pages {}..={}",
+                    result.pages[run[0]].index,
+                    result.pages[run[run.len() - 1]].index
+                );
             }
         }
 
-        let mut first_page_headers: HashSet<String> = HashSet::new();
-
-        // Now apply changes to the pages
-        for (seq_idx, &page_idx) in toc_page_indices.iter().enumerate() {
-            if let Some(page) = result.pages.get_mut(page_idx) {
-                let toc_links = toc_links_by_page.get(&page_idx).unwrap();
-                let unknown_lines = unknown_lines_by_page.get(&page_idx).unwrap();
+        // Now apply changes to the pages. Headers are learned on the first page of each run.
+        for run in &runs {
+            let mut first_page_headers: HashSet<String> = HashSet::new();
+            let mut current_list = TocList::Contents;
+            for (seq_idx, &page_idx) in run.iter().enumerate() {
+                let Some(scan) = scans[page_idx].as_mut() else {
+                    continue;
+                };
+                current_list = assign_lists(scan, current_list);
+                let links = std::mem::take(&mut scan.toc_links);
+                for list in [TocList::Contents, TocList::Figures, TocList::Tables] {
+                    let mut group: Vec<TocLink> =
+                        links.iter().filter(|l| l.list == list).cloned().collect();
+                    if group.is_empty() {
+                        continue;
+                    }
+                    link_levelers
+                        .entry(list)
+                        .or_insert_with(LinkLeveler::new)
+                        .level_page_items(&mut group);
+                    scan.toc_links.extend(group);
+                }
+                let page = &mut result.pages[page_idx];
+                page.items = std::mem::take(&mut scan.items);
+                let toc_links = &scan.toc_links;
+                let unknown_lines = &scan.unknown_lines;
 
                 // Filter unknown lines that are contained in TOC links (duplicates)
                 let mut valid_unknown_lines = HashSet::new();
@@ -382,33 +227,8 @@ impl Transformation for DetectTOC {
                     }
 
                     // Remove repetitive headers found on the first TOC page (Dynamic with partial matching)
-                    let check_repetitive = |text: &str| -> bool {
-                        if first_page_headers.contains(text) {
-                            return true;
-                        }
-                        // Check if text starts with any of the first page headers (e.g. "Table of Contents 5" starts with "Table of Contents")
-                        // But ensure the header is significant (e.g. > 3 chars) to avoid matching "1" against "10"
-                        if first_page_headers
-                            .iter()
-                            .any(|h| h.len() > 3 && text.starts_with(h))
-                        {
-                            return true;
-                        }
-
-                        // Check normalized version (split by |)
-                        let text_norm = text.split('|').next().unwrap_or(text).trim();
-                        if first_page_headers.contains(text_norm) {
-                            return true;
-                        }
-                        if first_page_headers
-                            .iter()
-                            .any(|h| h.len() > 3 && text_norm.starts_with(h))
-                        {
-                            return true;
-                        }
-
-                        false
-                    };
+                    let check_repetitive =
+                        |text: &str| is_repetitive_header(&first_page_headers, text);
 
                     if seq_idx > 0 && check_repetitive(clean_line_text) {
                         if self.verbose {
@@ -486,32 +306,7 @@ impl Transformation for DetectTOC {
                             .collect::<String>();
                         let clean_text = text.trim();
 
-                        // Reuse check_repetitive logic? We can't easily reuse the closure from previous scope.
-                        // Duplicate logic briefly or move to method.
-                        // Inline check:
-                        let is_repetitive = {
-                            if first_page_headers.contains(clean_text) {
-                                true
-                            } else if first_page_headers
-                                .iter()
-                                .any(|h| h.len() > 3 && clean_text.starts_with(h))
-                            {
-                                true
-                            } else {
-                                let text_norm =
-                                    clean_text.split('|').next().unwrap_or(clean_text).trim();
-                                if first_page_headers.contains(text_norm) {
-                                    true
-                                } else if first_page_headers
-                                    .iter()
-                                    .any(|h| h.len() > 3 && text_norm.starts_with(h))
-                                {
-                                    true
-                                } else {
-                                    false
-                                }
-                            }
-                        };
+                        let is_repetitive = is_repetitive_header(&first_page_headers, clean_text);
 
                         if seq_idx > 0 && is_repetitive {
                             continue;
@@ -521,7 +316,7 @@ impl Transformation for DetectTOC {
                             continue;
                         }
 
-                        let ends_with_digit = Self::line_ends_with_digit(line);
+                        let ends_with_digit = has_page_ref(line);
                         if !ends_with_digit {
                             headline_idx = Some(i);
                             break;
@@ -536,13 +331,16 @@ impl Transformation for DetectTOC {
                     if let Some(link) = toc_link_map.get(&i) {
                         let mut item = link.line_item.clone();
                         item.block_type = BlockType::TocItem(link.level);
+                        item.toc_target = Some(TocTarget {
+                            page_label: link.page_label.clone(),
+                            slug: None,
+                            list: link.list,
+                        });
                         new_items.push(ItemType::LineItem(item));
                         continue;
                     }
 
-                    if valid_unknown_lines.contains(&i) {
-                        new_items.push(item);
-                    } else if Some(i) == headline_idx {
+                    if valid_unknown_lines.contains(&i) || Some(i) == headline_idx {
                         new_items.push(item);
                     }
                     // Else discarded
@@ -555,20 +353,345 @@ impl Transformation for DetectTOC {
 }
 
 impl DetectTOC {
-    fn line_ends_with_digit(line: &LineItem) -> bool {
-        let mut words: Vec<TextItem> = line.items.clone();
-        words.retain(|w| !w.text.chars().all(|c| c == '.'));
-
-        if let Some(last) = words.last() {
-            let original_text = &last.text;
-            // Remove trailing markdown
-            let trimmed_text = original_text.trim_end_matches(|c| c == '*' || c == '_' || c == ' ');
-            // Check if ends with digit
-            if let Some(c) = trimmed_text.chars().last() {
-                return c.is_digit(10);
+    /// Analyses a page as a potential TOC page. Returns `None` unless more than 75% of its
+    /// lines end with a page reference.
+    fn scan_page(&self, page: &Page, globals: &GlobalStats) -> Option<PageScan> {
+        let items = reattach_page_refs(&page.items);
+
+        let mut line_items_with_digits = 0;
+        let mut toc_links = Vec::new();
+        let mut unknown_lines = HashSet::new();
+
+        let mut last_words_without_number: Option<Vec<TextItem>> = None;
+        let mut last_line_idx: Option<usize> = None;
+        let mut last_y: Option<f64> = None;
+
+        let mut headline_item_idx: Option<usize> = None;
+        let mut has_title = false;
+
+        let mut processed_items = 0;
+
+        for (line_idx, item) in items.iter().enumerate() {
+            let ItemType::LineItem(line) = item else {
+                continue;
+            };
+            processed_items += 1;
+
+            let Some((mut words, page_label)) = split_page_ref(line) else {
+                if headline_item_idx.is_none() {
+                    headline_item_idx = Some(line_idx);
+                    has_title = is_toc_title(&join_line_items(line));
+                } else {
+                    if let Some(idx) = last_line_idx {
+                        // Previous line was also without number, mark it unknown
+                        unknown_lines.insert(idx);
+                    }
+                    let mut words = line.items.clone();
+                    words.retain(|w| !is_leader(&w.text));
+                    last_words_without_number = Some(words);
+                    last_line_idx = Some(line_idx);
+                    last_y = Some(line.y);
+                }
+                continue;
+            };
+
+            if let Some(prev_words) = last_words_without_number.take() {
+                // Check gap to avoid merging unrelated headers
+                let threshold = globals.most_used_distance * 1.5;
+                let gap = (last_y.unwrap_or(line.y) - line.y).abs();
+
+                if gap > threshold {
+                    // Don't merge. Treat previous as unknown (likely header)
+                    if let Some(idx) = last_line_idx {
+                        unknown_lines.insert(idx);
+                    }
+                } else {
+                    // Merge the wrapped title with the line that carries the page number
+                    let mut new_words = prev_words;
+                    new_words.extend(words);
+                    words = new_words;
+                }
+            }
+
+            // Create a new LineItem for the TOC link
+            let mut link_line_item = line.clone();
+            link_line_item.items = words;
+
+            // Update x/y to match the first item (in case of multiline merge where first line starts earlier)
+            if let Some(first_item) = link_line_item.items.first() {
+                link_line_item.x = first_item.x;
+                link_line_item.y = first_item.y;
+            }
+
+            toc_links.push(TocLink {
+                line_item: link_line_item,
+                level: 0, // Will be set by leveler
+                original_line_index: line_idx,
+                page_label,
+                list: TocList::Contents,
+            });
+
+            line_items_with_digits += 1;
+        }
+
+        if processed_items == 0
+            || (line_items_with_digits as f64 * 100.0 / processed_items as f64) <= 75.0
+        {
+            return None;
+        }
+
+        if self.verbose {
+            crate::lgger!("DEBUG: Detected TOC page {}", page.index);
+        }
+
+        Some(PageScan {
+            items,
+            toc_links,
+            unknown_lines,
+            has_title,
+        })
+    }
+}
+
+/// Characters used for leaders between a TOC title and its page number.
+const LEADER_CHARS: [char; 3] = ['.', '\u{2026}', '\u{b7}'];
+
+/// Whether a word is only leader dots (possibly spaced out, e.g. `. . . .`) or empty.
+fn is_leader(text: &str) -> bool {
+    text.chars()
+        .all(|c| c.is_whitespace() || LEADER_CHARS.contains(&c))
+}
+
+fn has_page_ref(line: &LineItem) -> bool {
+    split_page_ref(line).is_some()
+}
+
+/// Splits a trailing page reference (arabic or roman numerals, after optional dot leaders) off a
+/// TOC line. Returns the remaining words, with leaders removed, and the page label.
+fn split_page_ref(line: &LineItem) -> Option<(Vec<TextItem>, String)> {
+    let mut words: Vec<TextItem> = line.items.clone();
+    let had_leader_items = words
+        .iter()
+        .any(|w| is_leader(&w.text) && !w.text.trim().is_empty());
+    words.retain(|w| !is_leader(&w.text));
+
+    let last_idx = words.len().checked_sub(1)?;
+    let original_text = words[last_idx].text.clone();
+    // Trailing emphasis markers and spaces are not part of the number
+    let trimmed_text = original_text.trim_end_matches(['*', '_', ' ']);
+    let suffix = &original_text[trimmed_text.len()..];
+
+    let digits_start = trimmed_text
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| c.is_ascii_digit())
+        .last()
+        .map(|(i, _)| i);
+
+    let (prefix, label) = match digits_start {
+        Some(start) => trimmed_text.split_at(start),
+        None => {
+            // Roman numerals must be a separate word following leaders or a wide gap, so that
+            // titles like "Appendix C" are not taken for page references
+            let start = trimmed_text
+                .char_indices()
+                .rev()
+                .take_while(|(_, c)| !c.is_whitespace() && !LEADER_CHARS.contains(c))
+                .last()
+                .map_or(trimmed_text.len(), |(i, _)| i);
+            let (prefix, label) = trimmed_text.split_at(start);
+            let follows_leader = prefix.trim_end().ends_with(LEADER_CHARS);
+            let follows_gap = prefix.is_empty()
+                && last_idx > 0
+                && words[last_idx].x - (words[last_idx - 1].x + words[last_idx - 1].width)
+                    > words[last_idx].font_size;
+            if !is_roman(label) || !(follows_leader || had_leader_items || follows_gap) {
+                return None;
+            }
+            (prefix, label)
+        }
+    };
+
+    let label = label.to_string();
+    words[last_idx].text = format!("{}{}", prefix, suffix);
+
+    // Remove empty words (e.g. if word was just digits) and leaders left before them
+    while let Some(last) = words.last_mut() {
+        last.text = last
+            .text
+            .trim_end_matches(|c: char| c.is_whitespace() || LEADER_CHARS.contains(&c))
+            .to_string();
+        if !last.text.is_empty() {
+            break;
+        }
+        words.pop();
+    }
+
+    Some((words, label))
+}
+
+/// Tags each link of the page with the list it belongs to: the list opened by the closest title
+/// above it (carried over from previous pages), unless the entry itself reads like a caption.
+/// Returns the list that is still open at the end of the page.
+fn assign_lists(scan: &mut PageScan, mut current: TocList) -> TocList {
+    let titles: Vec<(usize, TocList)> = scan
+        .items
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, item)| match item {
+            ItemType::LineItem(line) if !has_page_ref(line) => {
+                toc_list_of_title(&join_line_items(line)).map(|list| (idx, list))
             }
+            _ => None,
+        })
+        .collect();
+
+    let mut titles = titles.into_iter().peekable();
+    for link in &mut scan.toc_links {
+        while let Some((_, list)) = titles.next_if(|(idx, _)| *idx <= link.original_line_index) {
+            current = list;
         }
+        let text = link
+            .line_item
+            .items
+            .iter()
+            .map(|i| i.text.as_str())
+            .collect::<Vec<_>>()
+            .join(" ");
+        link.list = caption_label(&text).map_or(current, |label| label.list);
+    }
+    titles.last().map_or(current, |(_, list)| list)
+}
+
+/// List opened by a TOC title line, if it is one.
+fn toc_list_of_title(text: &str) -> Option<TocList> {
+    if !is_toc_title(text) {
+        return None;
+    }
+    let text = text.to_lowercase();
+    if text.contains("figure") || text.contains("illustration") {
+        Some(TocList::Figures)
+    } else if text.contains("table") && !text.contains("contents") {
+        Some(TocList::Tables)
+    } else {
+        Some(TocList::Contents)
+    }
+}
+
+/// Titles that open a TOC-like list.
+fn is_toc_title(text: &str) -> bool {
+    let text = text.split('|').next().unwrap_or(text).trim().to_lowercase();
+    text.contains("contents")
+        || text.starts_with("list of")
+        || matches!(text.as_str(), "figures" | "tables" | "illustrations")
+}
+
+/// Right-aligned page numbers are sometimes separate text objects that end up on a line of their
+/// own. Moves each such number back onto the title line at the same height.
+fn reattach_page_refs(items: &[ItemType]) -> Vec<ItemType> {
+    let mut items = items.to_vec();
+    let mut i = 0;
+    while i < items.len() {
+        let target = match &items[i] {
+            ItemType::LineItem(number_line)
+                if split_page_ref(number_line).is_some_and(|(words, _)| words.is_empty()) =>
+            {
+                items.iter().position(|item| match item {
+                    ItemType::LineItem(line) => {
+                        line.x < number_line.x
+                            && (line.y - number_line.y).abs() < line.height.max(1.0) * 0.5
+                            && !has_page_ref(line)
+                    }
+                    _ => false,
+                })
+            }
+            _ => None,
+        };
+
+        let Some(target) = target else {
+            i += 1;
+            continue;
+        };
+        let ItemType::LineItem(number_line) = items.remove(i) else {
+            unreachable!("checked above")
+        };
+        let target = if target > i { target - 1 } else { target };
+        if let ItemType::LineItem(line) = &mut items[target] {
+            line.width = (number_line.x + number_line.width) - line.x;
+            line.items.extend(number_line.items);
+        }
+    }
+    items
+}
+
+/// Checks whether `text` repeats one of the headers learned on the first TOC page,
+/// either exactly or as a prefix (e.g. "Table of Contents 5"), also trying the part before `|`.
+fn is_repetitive_header(headers: &HashSet<String>, text: &str) -> bool {
+    let matches = |candidate: &str| {
+        headers.contains(candidate)
+            || headers
+                .iter()
+                .any(|h| h.len() > 3 && candidate.starts_with(h.as_str()))
+    };
+    let text_norm = text.split('|').next().unwrap_or(text).trim();
+    matches(text) || matches(text_norm)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(words: &[(&str, f64, f64)]) -> LineItem {
+        LineItem {
+            items: words
+                .iter()
+                .map(|&(text, x, width)| TextItem {
+                    text: text.to_string(),
+                    x,
+                    width,
+                    font_size: 10.0,
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn page_ref(words: &[(&str, f64, f64)]) -> Option<(String, String)> {
+        let (rest, label) = split_page_ref(&line(words))?;
+        let text = rest.iter().map(|w| w.text.as_str()).collect::<String>();
+        Some((text, label))
+    }
+
+    #[test]
+    fn splits_arabic_and_roman_page_refs() {
+        assert_eq!(
+            page_ref(&[("Introduction ........ 12", 50.0, 300.0)]),
+            Some(("Introduction".to_string(), "12".to_string()))
+        );
+        assert_eq!(
+            page_ref(&[("Preface . . . . . xiv", 50.0, 300.0)]),
+            Some(("Preface".to_string(), "xiv".to_string()))
+        );
+        // Separate leader and number items
+        assert_eq!(
+            page_ref(&[
+                ("Foreword", 50.0, 60.0),
+                ("......", 120.0, 200.0),
+                ("ix", 330.0, 10.0)
+            ]),
+            Some(("Foreword".to_string(), "ix".to_string()))
+        );
+        // A roman numeral set apart by a wide gap
+        assert_eq!(
+            page_ref(&[("Contributors", 50.0, 80.0), ("vii", 400.0, 15.0)]),
+            Some(("Contributors".to_string(), "vii".to_string()))
+        );
+    }
 
-        false
+    #[test]
+    fn keeps_roman_numerals_that_belong_to_the_title() {
+        assert_eq!(page_ref(&[("Appendix C", 50.0, 80.0)]), None);
+        assert_eq!(page_ref(&[("Part IV", 50.0, 60.0)]), None);
     }
 }
@@ -0,0 +1,169 @@
+use crate::models::{ItemType, ParseResult, TextItem};
+use crate::transformations::common::{is_cjk, Transformation};
+
+/// Finds Chinese and Japanese text set in vertical columns. Its glyphs stand upright, so the
+/// text matrix reads as horizontal; columns show up as tall text objects, or as single glyphs
+/// stacked top to bottom. Such items are marked `vertical`, and `CompactLines` then builds one
+/// line per column, read top to bottom, with the columns ordered right to left.
+pub struct DetectVerticalWriting {
+    pub verbose: bool,
+}
+
+impl Transformation for DetectVerticalWriting {
+    fn transform(&self, result: &mut ParseResult) {
+        let mut detected = Vec::new();
+
+        for page in &mut result.pages {
+            let mut items: Vec<&mut TextItem> = page
+                .items
+                .iter_mut()
+                .filter_map(|item| match item {
+                    ItemType::TextItem(text_item) if text_item.is_horizontal() => Some(text_item),
+                    _ => None,
+                })
+                .collect();
+
+            // Glyphs placed one by one: vertical writing if they mostly step down, not right
+            let (mut vertical, mut horizontal) = (0, 0);
+            for pair in items.windows(2) {
+                if !is_cjk_glyph(pair[0]) || !is_cjk_glyph(pair[1]) {
+                    continue;
+                }
+                if stacked(pair[0], pair[1]) {
+                    vertical += 1;
+                } else if side_by_side(pair[0], pair[1]) {
+                    horizontal += 1;
+                }
+            }
+            let columns_of_glyphs = vertical > horizontal;
+
+            let mut in_column = vec![false; items.len()];
+            for i in 0..items.len() {
+                let item = &*items[i];
+                if is_column(item) {
+                    in_column[i] = true;
+                }
+                if columns_of_glyphs
+                    && i + 1 < items.len()
+                    && is_cjk_glyph(item)
+                    && is_cjk_glyph(items[i + 1])
+                    && stacked(item, items[i + 1])
+                {
+                    in_column[i] = true;
+                    in_column[i + 1] = true;
+                }
+            }
+
+            let mut count = 0;
+            for (item, vertical) in items.iter_mut().zip(in_column) {
+                if vertical {
+                    item.vertical = true;
+                    count += 1;
+                }
+            }
+            if count > 0 {
+                detected.push(page.index + 1);
+            }
+        }
+
+        if self.verbose {
+            crate::lgger!(
+                "DetectVerticalWriting: vertical text on {} pages {:?}",
+                detected.len(),
+                detected
+            );
+        }
+    }
+}
+
+/// A single CJK character, as extracted from PDFs that place each glyph on its own.
+fn is_cjk_glyph(item: &TextItem) -> bool {
+    let mut chars = item.text.trim().chars();
+    chars.next().is_some_and(is_cjk) && chars.next().is_none()
+}
+
+/// A run of CJK text much taller than it is wide: a whole column in one text object.
+fn is_column(item: &TextItem) -> bool {
+    let text = item.text.trim();
+    text.chars().count() > 1
+        && text.chars().filter(|&c| is_cjk(c)).count() * 2 > text.chars().count()
+        && item.height > item.width * 1.5
+}
+
+/// `next` sits right below `prev` in the same column.
+fn stacked(prev: &TextItem, next: &TextItem) -> bool {
+    let size = prev.font_size.max(1.0);
+    let step = prev.y - next.y;
+    (prev.x - next.x).abs() < size * 0.5 && step >= size * 0.5 && step <= size * 2.0
+}
+
+/// `next` follows `prev` on the same horizontal line.
+fn side_by_side(prev: &TextItem, next: &TextItem) -> bool {
+    let size = prev.font_size.max(1.0);
+    let step = next.x - prev.x;
+    (prev.y - next.y).abs() < size * 0.5 && step > 0.0 && step <= size * 2.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{GlobalStats, Page};
+
+    fn item(text: &str, x: f64, y: f64, width: f64, height: f64) -> ItemType {
+        ItemType::TextItem(TextItem {
+            text: text.to_string(),
+            x,
+            y,
+            width,
+            height,
+            font_size: 10.0,
+            ..Default::default()
+        })
+    }
+
+    fn detect(items: Vec<ItemType>) -> Vec<bool> {
+        let mut result = ParseResult {
+            pages: vec![Page {
+                items,
+                ..Default::default()
+            }],
+            globals: GlobalStats::default(),
+        };
+        DetectVerticalWriting { verbose: false }.transform(&mut result);
+        result.pages[0]
+            .items
+            .iter()
+            .map(|item| matches!(item, ItemType::TextItem(text_item) if text_item.vertical))
+            .collect()
+    }
+
+    #[test]
+    fn marks_stacked_glyphs_as_vertical() {
+        let marked = detect(vec![
+            item("縦", 100.0, 700.0, 10.0, 10.0),
+            item("書", 100.0, 688.0, 10.0, 10.0),
+            item("き", 100.0, 676.0, 10.0, 10.0),
+        ]);
+        assert_eq!(marked, [true, true, true]);
+    }
+
+    #[test]
+    fn keeps_horizontal_glyphs_horizontal() {
+        let marked = detect(vec![
+            item("横", 100.0, 700.0, 10.0, 10.0),
+            item("書", 111.0, 700.0, 10.0, 10.0),
+            item("き", 122.0, 700.0, 10.0, 10.0),
+        ]);
+        assert_eq!(marked, [false, false, false]);
+    }
+
+    #[test]
+    fn marks_tall_cjk_runs_as_columns() {
+        let marked = detect(vec![
+            item("縦書きの列", 100.0, 700.0, 10.0, 50.0),
+            item("Latin", 120.0, 700.0, 10.0, 50.0),
+            item("横書き", 140.0, 700.0, 30.0, 10.0),
+        ]);
+        assert_eq!(marked, [true, false, false]);
+    }
+}
@@ -0,0 +1,155 @@
+use crate::models::{
+    BlockType, FontProperties, ItemType, LineItem, ParseResult, RenderMode, TextItem, TocList,
+    TocTarget,
+};
+use crate::transformations::common::{
+    clean_line_text, heading_slugs, join_line_items, Transformation,
+};
+
+/// Builds a linked TOC from the detected headings and inserts it at a `[TOC]` placeholder line,
+/// or at the top of the document if the PDF has no TOC of its own.
+pub struct GenerateTOC {
+    pub verbose: bool,
+    /// Number of heading levels to include, counted from the highest level present.
+    pub max_depth: usize,
+}
+
+const PLACEHOLDER: &str = "[TOC]";
+
+impl Transformation for GenerateTOC {
+    fn transform(&self, result: &mut ParseResult) {
+        let placeholder = find_placeholder(result);
+        let has_toc = result
+            .pages
+            .iter()
+            .flat_map(|p| &p.items)
+            .any(|item| match item {
+                ItemType::LineItem(line) => matches!(line.block_type, BlockType::TocItem(_)),
+                _ => false,
+            });
+        if placeholder.is_none() && has_toc {
+            if self.verbose {
+                crate::lgger!("GenerateTOC: document already has a TOC, skipping");
+            }
+            return;
+        }
+
+        let slugs = heading_slugs(result);
+        let mut headings: Vec<(usize, String, String, u16)> = Vec::new();
+        for (page_idx, page) in result.pages.iter().enumerate() {
+            for (item_idx, item) in page.items.iter().enumerate() {
+                if let ItemType::LineItem(line) = item {
+                    if let (Some(level), Some(slug)) = (
+                        line.block_type.heading_level(),
+                        slugs.get(&(page_idx, item_idx)),
+                    ) {
+                        let text = clean_line_text(join_line_items(line));
+                        headings.push((level, text, slug.clone(), page.index));
+                    }
+                }
+            }
+        }
+
+        let Some(top_level) = headings.iter().map(|h| h.0).min() else {
+            if self.verbose {
+                crate::lgger!("GenerateTOC: no headings found");
+            }
+            return;
+        };
+        headings.retain(|h| h.0 < top_level + self.max_depth.max(1));
+
+        let Some((page_idx, item_idx)) = placeholder.or_else(|| {
+            result
+                .pages
+                .iter()
+                .position(|p| !p.items.is_empty())
+                .map(|page_idx| (page_idx, 0))
+        }) else {
+            return;
+        };
+
+        // Lay the entries out at body line spacing, ending just above the line they precede
+        let distance = result.globals.most_used_distance.max(1.0);
+        let page = &mut result.pages[page_idx];
+        let anchor_y = match page.items.get(item_idx) {
+            Some(ItemType::LineItem(line)) if placeholder.is_some() => line.y + distance,
+            Some(ItemType::LineItem(line)) => line.y + 2.0 * distance,
+            _ => 0.0,
+        };
+        let font = result.globals.most_used_font.clone();
+        let height = result.globals.most_used_height;
+        let count = headings.len();
+
+        let entries = headings
+            .into_iter()
+            .enumerate()
+            .map(|(i, (level, text, slug, page_index))| {
+                let y = anchor_y + (count - i) as f64 * distance;
+                ItemType::LineItem(LineItem {
+                    items: vec![TextItem {
+                        text,
+                        x: 0.0,
+                        y,
+                        width: 0.0,
+                        height,
+                        font: font.clone(),
+                        font_size: height,
+                        format: None,
+                        font_properties: FontProperties::default(),
+                        fill_color: None,
+                        stroke_color: None,
+                        render_mode: RenderMode::Fill,
+                        highlighted: false,
+                        angle: 0.0,
+                        vertical: false,
+                    }],
+                    x: 0.0,
+                    y,
+                    width: 0.0,
+                    height,
+                    block_type: BlockType::TocItem(level - top_level),
+                    toc_target: Some(TocTarget {
+                        page_label: (page_index + 1).to_string(),
+                        slug: Some(slug),
+                        list: TocList::Contents,
+                    }),
+                    anchor: None,
+                    figure: None,
+                    rtl: false,
+                    cells: Vec::new(),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        if self.verbose {
+            crate::lgger!(
+                "GenerateTOC: inserting {} entries on page {}",
+                count,
+                page.index
+            );
+        }
+
+        let end = if placeholder.is_some() {
+            item_idx + 1
+        } else {
+            item_idx
+        };
+        page.items.splice(item_idx..end, entries);
+    }
+}
+
+/// Position of the first line that consists of just `[TOC]`.
+fn find_placeholder(result: &ParseResult) -> Option<(usize, usize)> {
+    let is_placeholder = |item: &ItemType| match item {
+        ItemType::LineItem(line) => join_line_items(line).trim() == PLACEHOLDER,
+        _ => false,
+    };
+    result
+        .pages
+        .iter()
+        .enumerate()
+        .find_map(|(page_idx, page)| {
+            let item_idx = page.items.iter().position(is_placeholder)?;
+            Some((page_idx, item_idx))
+        })
+}
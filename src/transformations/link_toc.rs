@@ -0,0 +1,552 @@
+use crate::models::{BlockType, ItemType, LineItem, ParseResult, TocList};
+use crate::transformations::common::{
+    caption_anchor, caption_label, heading_slugs, join_line_items, roman_value, Transformation,
+};
+use std::collections::{HashMap, HashSet};
+
+/// Matches TOC entries to the headings found in the body by text similarity and printed
+/// page number, and stores the heading's anchor slug on the entry. Entries without a heading
+/// promote a matching body line on their target page, and consistent TOC levels correct
+/// heading levels that disagree with them.
+pub struct LinkTOC {
+    pub verbose: bool,
+}
+
+/// Minimum similarity for a TOC entry to link to a heading.
+const MIN_SIMILARITY: f64 = 0.6;
+/// Similarity needed to promote a body line or to vote on page offsets and levels.
+const STRONG_SIMILARITY: f64 = 0.9;
+
+struct Entry {
+    pos: (usize, usize),
+    level: usize,
+    text: String,
+    words: Vec<String>,
+    printed_page: Option<PrintedPage>,
+    list: TocList,
+}
+
+/// Page number printed in a TOC entry. Front matter is usually numbered in roman numerals
+/// and the body in arabic ones, each with its own offset from the page index.
+#[derive(Clone, Copy)]
+struct PrintedPage {
+    number: i64,
+    roman: bool,
+}
+
+impl PrintedPage {
+    fn parse(label: &str) -> Option<Self> {
+        match label.parse() {
+            Ok(number) => Some(PrintedPage {
+                number,
+                roman: false,
+            }),
+            Err(_) => roman_value(label).map(|number| PrintedPage {
+                number,
+                roman: true,
+            }),
+        }
+    }
+}
+
+/// Differences between page indices and printed arabic and roman page numbers.
+#[derive(Debug, Default)]
+struct PageOffsets {
+    arabic: Option<i64>,
+    roman: Option<i64>,
+}
+
+impl PageOffsets {
+    /// Page index that an entry's printed page number points to, if its offset is known.
+    fn page_of(&self, entry: &Entry) -> Option<i64> {
+        let printed = entry.printed_page?;
+        let offset = if printed.roman {
+            self.roman
+        } else {
+            self.arabic
+        };
+        offset.map(|offset| printed.number + offset)
+    }
+}
+
+struct Caption {
+    pos: (usize, usize),
+    page_index: i64,
+    anchor: String,
+    list: TocList,
+    words: Vec<String>,
+}
+
+struct Heading {
+    pos: (usize, usize),
+    page_index: i64,
+    words: Vec<String>,
+}
+
+impl Transformation for LinkTOC {
+    fn transform(&self, result: &mut ParseResult) {
+        let (entries, list_entries): (Vec<Entry>, Vec<Entry>) = collect_entries(result)
+            .into_iter()
+            .partition(|e| e.list == TocList::Contents);
+        let toc_pages: HashSet<usize> = entries
+            .iter()
+            .chain(&list_entries)
+            .map(|e| e.pos.0)
+            .collect();
+        let headings: Vec<Heading> = collect_headings(result)
+            .into_iter()
+            .filter(|h| !toc_pages.contains(&h.pos.0))
+            .collect();
+
+        // Printed page numbers usually differ from page indices by the length of the front matter
+        let offset_of = |roman: bool| {
+            most_common(entries.iter().flat_map(|entry| {
+                headings
+                    .iter()
+                    .filter(|h| h.pos.0 > entry.pos.0)
+                    .filter(|h| similarity(&entry.words, &h.words) >= STRONG_SIMILARITY)
+                    .filter_map(|h| {
+                        let printed = entry.printed_page.filter(|p| p.roman == roman)?;
+                        Some(h.page_index - printed.number)
+                    })
+                    .collect::<Vec<_>>()
+            }))
+        };
+        let offset = PageOffsets {
+            arabic: offset_of(false),
+            roman: offset_of(true),
+        };
+        if self.verbose {
+            crate::lgger!(
+                "LinkTOC: {} entries, page offset {:?}",
+                entries.len(),
+                offset
+            );
+        }
+
+        // Each heading is linked at most once; the page number breaks ties between equal titles
+        let mut used = vec![false; headings.len()];
+        let mut matches: Vec<Option<(usize, f64)>> = Vec::with_capacity(entries.len());
+        for entry in &entries {
+            let expected_page = offset.page_of(entry);
+            let best = headings
+                .iter()
+                .enumerate()
+                .filter(|(i, h)| !used[*i] && h.pos.0 > entry.pos.0)
+                .map(|(i, h)| {
+                    let text_score = similarity(&entry.words, &h.words);
+                    let page_score = match expected_page {
+                        Some(page) if page == h.page_index => 0.3,
+                        Some(page) if (page - h.page_index).abs() == 1 => 0.1,
+                        _ => 0.0,
+                    };
+                    (i, text_score, text_score + page_score)
+                })
+                .filter(|&(_, text_score, _)| text_score >= MIN_SIMILARITY)
+                .fold(
+                    None,
+                    |best: Option<(usize, f64, f64)>, candidate| match best {
+                        Some(b) if b.2 >= candidate.2 => Some(b),
+                        _ => Some(candidate),
+                    },
+                );
+            if let Some((i, _, _)) = best {
+                used[i] = true;
+            }
+            matches.push(best.map(|(i, text_score, _)| (i, text_score)));
+        }
+
+        // Heading level that each TOC level maps to, learned from confident matches
+        let mut level_votes: HashMap<usize, Vec<usize>> = HashMap::new();
+        for (entry, matched) in entries.iter().zip(&matches) {
+            if let Some((i, score)) = matched {
+                if *score >= STRONG_SIMILARITY {
+                    if let Some(level) = heading_level_at(result, headings[*i].pos) {
+                        level_votes.entry(entry.level).or_default().push(level);
+                    }
+                }
+            }
+        }
+        let level_map: HashMap<usize, usize> = level_votes
+            .into_iter()
+            .filter(|(_, votes)| votes.len() >= 3)
+            .filter_map(|(toc_level, votes)| {
+                let total = votes.len();
+                let (level, count) = mode_with_count(votes)?;
+                (count * 2 > total).then_some((toc_level, level))
+            })
+            .collect();
+
+        let mut targets: Vec<Option<(usize, usize)>> = Vec::with_capacity(entries.len());
+        let mut promoted = 0;
+        let mut corrected = 0;
+        for (entry, matched) in entries.iter().zip(&matches) {
+            let level = level_map
+                .get(&entry.level)
+                .copied()
+                .unwrap_or(entry.level + 1);
+            match matched {
+                Some((i, score)) => {
+                    let pos = headings[*i].pos;
+                    if *score >= STRONG_SIMILARITY && level_map.contains_key(&entry.level) {
+                        if let Some(line) = line_at(result, pos) {
+                            if line.block_type.heading_level() != Some(level) {
+                                line.block_type = BlockType::heading(level);
+                                corrected += 1;
+                            }
+                        }
+                    }
+                    targets.push(Some(pos));
+                }
+                None => {
+                    let page = offset.page_of(entry);
+                    let found = page.and_then(|page| find_body_line(result, page, &entry.words));
+                    if let Some(pos) = found {
+                        if let Some(line) = line_at(result, pos) {
+                            line.block_type = BlockType::heading(level);
+                            promoted += 1;
+                        }
+                    }
+                    targets.push(found);
+                }
+            }
+        }
+
+        let slug_by_pos = heading_slugs(result);
+
+        let mut linked = 0;
+        for (entry, target) in entries.iter().zip(targets) {
+            let slug = target.and_then(|pos| slug_by_pos.get(&pos).cloned());
+            if let Some(toc_target) = line_at(result, entry.pos).and_then(|l| l.toc_target.as_mut())
+            {
+                linked += usize::from(slug.is_some());
+                toc_target.slug = slug;
+            }
+        }
+
+        let linked_captions = link_captions(result, &list_entries, &toc_pages, &offset);
+
+        if self.verbose {
+            crate::lgger!(
+                "LinkTOC: linked {}/{} entries, promoted {} headings, corrected {} levels",
+                linked,
+                entries.len(),
+                promoted,
+                corrected
+            );
+            if !list_entries.is_empty() {
+                crate::lgger!(
+                    "LinkTOC: linked {}/{} figure and table entries to captions",
+                    linked_captions,
+                    list_entries.len()
+                );
+            }
+        }
+    }
+}
+
+/// Links entries of lists of figures and tables to the matching caption lines, by caption
+/// number when the entry has one and by text otherwise, and gives each linked caption an anchor.
+fn link_captions(
+    result: &mut ParseResult,
+    entries: &[Entry],
+    toc_pages: &HashSet<usize>,
+    offset: &PageOffsets,
+) -> usize {
+    if entries.is_empty() {
+        return 0;
+    }
+    let captions = collect_captions(result, toc_pages);
+    let mut used = vec![false; captions.len()];
+    let mut linked = 0;
+
+    for entry in entries {
+        // Entries often drop the "Figure" prefix and only keep the number ("3-2 Request flow")
+        let entry_label = caption_label(&entry.text).or_else(|| {
+            let prefixed = match entry.list {
+                TocList::Tables => format!("Table {}", entry.text),
+                _ => format!("Figure {}", entry.text),
+            };
+            caption_label(&prefixed)
+        });
+        let entry_anchor = entry_label.as_ref().map(caption_anchor);
+        let entry_words = caption_words(&entry.words, entry_label.is_some());
+        let expected_page = offset.page_of(entry);
+
+        let best = captions
+            .iter()
+            .enumerate()
+            .filter(|(i, c)| !used[*i] && c.list == entry.list && c.pos.0 > entry.pos.0)
+            .map(|(i, c)| {
+                let text_score = if entry_anchor.as_ref() == Some(&c.anchor) {
+                    1.0
+                } else {
+                    similarity(&entry_words, &c.words)
+                };
+                let page_score = match expected_page {
+                    Some(page) if page == c.page_index => 0.3,
+                    _ => 0.0,
+                };
+                (i, text_score, text_score + page_score)
+            })
+            .filter(|&(_, text_score, _)| text_score >= MIN_SIMILARITY)
+            .fold(
+                None,
+                |best: Option<(usize, f64, f64)>, candidate| match best {
+                    Some(b) if b.2 >= candidate.2 => Some(b),
+                    _ => Some(candidate),
+                },
+            );
+
+        let Some((i, _, _)) = best else {
+            continue;
+        };
+        used[i] = true;
+        let caption = &captions[i];
+        if let Some(line) = line_at(result, caption.pos) {
+            line.anchor = Some(caption.anchor.clone());
+        }
+        if let Some(toc_target) = line_at(result, entry.pos).and_then(|l| l.toc_target.as_mut()) {
+            toc_target.slug = Some(caption.anchor.clone());
+            linked += 1;
+        }
+    }
+    linked
+}
+
+fn collect_captions(result: &ParseResult, toc_pages: &HashSet<usize>) -> Vec<Caption> {
+    let mut captions = Vec::new();
+    for (page_idx, page) in result.pages.iter().enumerate() {
+        if toc_pages.contains(&page_idx) {
+            continue;
+        }
+        for (item_idx, item) in page.items.iter().enumerate() {
+            let ItemType::LineItem(line) = item else {
+                continue;
+            };
+            if !matches!(line.block_type, BlockType::Paragraph | BlockType::Caption) {
+                continue;
+            }
+            let text = join_line_items(line);
+            if let Some(label) = caption_label(&text) {
+                captions.push(Caption {
+                    pos: (page_idx, item_idx),
+                    page_index: i64::from(page.index),
+                    anchor: caption_anchor(&label),
+                    list: label.list,
+                    words: caption_words(&words(&text), true),
+                });
+            }
+        }
+    }
+    captions
+}
+
+/// Caption text without the leading "Figure 3 2" label words.
+fn caption_words(words: &[String], labelled: bool) -> Vec<String> {
+    if !labelled {
+        return words.to_vec();
+    }
+    words
+        .iter()
+        .skip_while(|w| matches!(w.as_str(), "figure" | "fig" | "table" | "tab"))
+        .skip_while(|w| w.chars().all(|c| c.is_ascii_digit()) || w.len() == 1)
+        .cloned()
+        .collect()
+}
+
+fn collect_entries(result: &ParseResult) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    for (page_idx, page) in result.pages.iter().enumerate() {
+        for (item_idx, item) in page.items.iter().enumerate() {
+            if let ItemType::LineItem(line) = item {
+                if let BlockType::TocItem(level) = line.block_type {
+                    let text = join_line_items(line);
+                    let target = line.toc_target.as_ref();
+                    entries.push(Entry {
+                        pos: (page_idx, item_idx),
+                        level,
+                        words: words(&text),
+                        text,
+                        printed_page: target.and_then(|t| PrintedPage::parse(&t.page_label)),
+                        list: target.map_or(TocList::Contents, |t| t.list),
+                    });
+                }
+            }
+        }
+    }
+    entries
+}
+
+fn collect_headings(result: &ParseResult) -> Vec<Heading> {
+    let mut headings = Vec::new();
+    for (page_idx, page) in result.pages.iter().enumerate() {
+        for (item_idx, item) in page.items.iter().enumerate() {
+            if let ItemType::LineItem(line) = item {
+                if line.block_type.heading_level().is_some() {
+                    headings.push(Heading {
+                        pos: (page_idx, item_idx),
+                        page_index: i64::from(page.index),
+                        words: words(&join_line_items(line)),
+                    });
+                }
+            }
+        }
+    }
+    headings
+}
+
+/// First plain body line on `page_index` that reads like the TOC entry.
+fn find_body_line(
+    result: &ParseResult,
+    page_index: i64,
+    entry: &[String],
+) -> Option<(usize, usize)> {
+    let page_idx = result
+        .pages
+        .iter()
+        .position(|p| i64::from(p.index) == page_index)?;
+    result.pages[page_idx]
+        .items
+        .iter()
+        .enumerate()
+        .find_map(|(item_idx, item)| match item {
+            ItemType::LineItem(line)
+                if line.block_type == BlockType::Paragraph
+                    && similarity(entry, &words(&join_line_items(line))) >= STRONG_SIMILARITY =>
+            {
+                Some((page_idx, item_idx))
+            }
+            _ => None,
+        })
+}
+
+fn line_at(
+    result: &mut ParseResult,
+    (page_idx, item_idx): (usize, usize),
+) -> Option<&mut LineItem> {
+    match result.pages.get_mut(page_idx)?.items.get_mut(item_idx)? {
+        ItemType::LineItem(line) => Some(line),
+        _ => None,
+    }
+}
+
+fn heading_level_at(result: &ParseResult, (page_idx, item_idx): (usize, usize)) -> Option<usize> {
+    match result.pages.get(page_idx)?.items.get(item_idx)? {
+        ItemType::LineItem(line) => line.block_type.heading_level(),
+        _ => None,
+    }
+}
+
+/// Lowercased alphanumeric words, without dot leaders or punctuation.
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Dice coefficient over word sets, ignoring section numbers when that gives a better match
+/// ("2.1 Scope" in the TOC vs "Scope" as the heading).
+fn similarity(a: &[String], b: &[String]) -> f64 {
+    let strip = |words: &[String]| -> Vec<String> {
+        let start = words
+            .iter()
+            .take_while(|w| w.chars().all(|c| c.is_ascii_digit()))
+            .count();
+        words[start..].to_vec()
+    };
+    dice(a, b)
+        .max(dice(&strip(a), b))
+        .max(dice(a, &strip(b)))
+        .max(dice(&strip(a), &strip(b)))
+}
+
+fn dice(a: &[String], b: &[String]) -> f64 {
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    let common = a.iter().filter(|w| b.contains(w)).count();
+    (2.0 * common as f64 / (a.len() + b.len()) as f64).min(1.0)
+}
+
+fn most_common(values: impl Iterator<Item = i64>) -> Option<i64> {
+    mode_with_count(values.collect()).map(|(value, _)| value)
+}
+
+fn mode_with_count<T: std::hash::Hash + Eq + Copy + Ord>(values: Vec<T>) -> Option<(T, usize)> {
+    let mut counts: HashMap<T, usize> = HashMap::new();
+    for value in values {
+        *counts.entry(value).or_insert(0) += 1;
+    }
+    // Smallest value wins a tie so the result does not depend on hash order
+    counts
+        .into_iter()
+        .max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(&a.0)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{GlobalStats, Page, TextItem, TocTarget};
+
+    fn line(text: &str, block_type: BlockType, page_label: Option<&str>) -> ItemType {
+        ItemType::LineItem(LineItem {
+            items: vec![TextItem {
+                text: text.to_string(),
+                font_size: 10.0,
+                ..Default::default()
+            }],
+            block_type,
+            toc_target: page_label.map(|label| TocTarget {
+                page_label: label.to_string(),
+                slug: None,
+                list: TocList::Contents,
+            }),
+            ..Default::default()
+        })
+    }
+
+    fn page(index: u16, items: Vec<ItemType>) -> Page {
+        Page {
+            index,
+            items,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn follows_roman_page_numbers_into_the_front_matter() {
+        let entry = |text, label| line(text, BlockType::TocItem(0), Some(label));
+        let mut result = ParseResult {
+            pages: vec![
+                page(
+                    0,
+                    vec![
+                        entry("Preface", "ii"),
+                        entry("Acknowledgments", "iii"),
+                        entry("Introduction", "1"),
+                    ],
+                ),
+                page(1, vec![line("Preface", BlockType::H1, None)]),
+                // Printed "iii": only the roman offset leads here
+                page(2, vec![line("Acknowledgments", BlockType::Paragraph, None)]),
+                page(3, vec![line("Introduction", BlockType::H1, None)]),
+            ],
+            globals: GlobalStats::default(),
+        };
+        LinkTOC { verbose: false }.transform(&mut result);
+
+        let ItemType::LineItem(promoted) = &result.pages[2].items[0] else {
+            panic!("no line");
+        };
+        assert!(promoted.block_type.heading_level().is_some());
+        let ItemType::LineItem(entry) = &result.pages[0].items[1] else {
+            panic!("no entry");
+        };
+        assert_eq!(
+            entry.toc_target.as_ref().and_then(|t| t.slug.as_deref()),
+            Some("acknowledgments")
+        );
+    }
+}
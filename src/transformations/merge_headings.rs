@@ -0,0 +1,168 @@
+use crate::models::{BoundingBox, ItemType, LineItem, ParseResult};
+use crate::transformations::common::{join_line_items, section_depth, Transformation};
+
+/// Merges a heading that `CompactLines` split over several lines back into one `LineItem`.
+/// The next line joins when it has the same level and font, follows within normal line
+/// spacing for that font size and does not carry a section number of its own. Below a
+/// numbered heading, any heading level joins, since only the numbered line was re-levelled.
+/// The merged line covers the union of both boxes, which is what the source map records.
+pub struct MergeHeadings {
+    pub verbose: bool,
+}
+
+impl Transformation for MergeHeadings {
+    fn transform(&self, result: &mut ParseResult) {
+        let mut merged = 0;
+
+        for page in &mut result.pages {
+            let mut items: Vec<ItemType> = Vec::with_capacity(page.items.len());
+            for item in page.items.drain(..) {
+                if let (Some(ItemType::LineItem(prev)), ItemType::LineItem(line)) =
+                    (items.last_mut(), &item)
+                {
+                    if continues_heading(prev, line) {
+                        append_line(prev, line);
+                        merged += 1;
+                        continue;
+                    }
+                }
+                items.push(item);
+            }
+            page.items = items;
+        }
+
+        if self.verbose {
+            crate::lgger!("MergeHeadings: Merged {} wrapped heading lines", merged);
+        }
+    }
+}
+
+fn continues_heading(prev: &LineItem, line: &LineItem) -> bool {
+    if prev.block_type.heading_level().is_none() || line.block_type.heading_level().is_none() {
+        return false;
+    }
+    // DetectHeaders re-levels a numbered heading from its numbering, which only the first
+    // line carries; the wrapped rest keeps its font-size level
+    if line.block_type != prev.block_type && section_depth(&join_line_items(prev)).is_none() {
+        return false;
+    }
+    let (Some(prev_last), Some(first)) = (prev.items.last(), line.items.first()) else {
+        return false;
+    };
+    if prev_last.font != first.font || (prev_last.font_size - first.font_size).abs() >= 0.5 {
+        return false;
+    }
+    // Space between the bottom of the heading so far and the top of the next line: wrapped
+    // lines leave well under a line of it, a blank line means a new heading
+    let gap = (prev.y - prev.height) - line.y;
+    gap > -first.font_size * 0.5
+        && gap < first.font_size
+        && section_depth(&join_line_items(line)).is_none()
+}
+
+/// Appends the items of `line` to `prev`, keeping a word break between the two lines.
+fn append_line(prev: &mut LineItem, line: &LineItem) {
+    let hyphenated = prev
+        .items
+        .last()
+        .is_some_and(|item| item.text.trim_end().ends_with('-'));
+    let mut items = line.items.clone();
+    if let Some(first) = items.first_mut() {
+        if !hyphenated && !first.text.starts_with(' ') {
+            first.text.insert(0, ' ');
+        }
+    }
+    prev.items.extend(items);
+
+    let bbox = BoundingBox::of_line(prev).union(&BoundingBox::of_line(line));
+    prev.x = bbox.x;
+    prev.y = bbox.y;
+    prev.width = bbox.width;
+    prev.height = bbox.height;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{BlockType, GlobalStats, Page, TextItem};
+
+    fn heading(text: &str, level: usize, y: f64) -> ItemType {
+        ItemType::LineItem(LineItem {
+            items: vec![TextItem {
+                text: text.to_string(),
+                x: 50.0,
+                y,
+                width: 200.0,
+                height: 14.0,
+                font: "Serif-Bold".to_string(),
+                font_size: 14.0,
+                ..Default::default()
+            }],
+            x: 50.0,
+            y,
+            width: 200.0,
+            height: 14.0,
+            block_type: BlockType::heading(level),
+            ..Default::default()
+        })
+    }
+
+    fn merge(items: Vec<ItemType>) -> Vec<(Option<usize>, String)> {
+        let mut result = ParseResult {
+            pages: vec![Page {
+                items,
+                ..Default::default()
+            }],
+            globals: GlobalStats::default(),
+        };
+        MergeHeadings { verbose: false }.transform(&mut result);
+        result.pages[0]
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                ItemType::LineItem(line) => {
+                    Some((line.block_type.heading_level(), join_line_items(line)))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn merges_wrapped_heading_lines() {
+        let lines = merge(vec![
+            heading("Getting started with", 2, 700.0),
+            heading("the toolkit", 2, 684.0),
+        ]);
+        assert_eq!(
+            lines,
+            [(Some(2), "Getting started with the toolkit".to_string())]
+        );
+    }
+
+    #[test]
+    fn merges_the_rest_of_a_relevelled_numbered_heading() {
+        let lines = merge(vec![
+            heading("3.2 Configuring the", 3, 700.0),
+            heading("network stack", 2, 684.0),
+        ]);
+        assert_eq!(
+            lines,
+            [(Some(3), "3.2 Configuring the network stack".to_string())]
+        );
+    }
+
+    #[test]
+    fn keeps_separate_headings_apart() {
+        // Each line carries its own number
+        let lines = merge(vec![
+            heading("2.1 Setup", 3, 700.0),
+            heading("2.2 Usage", 3, 684.0),
+        ]);
+        assert_eq!(lines.len(), 2);
+
+        // A blank line in between
+        let lines = merge(vec![heading("Setup", 2, 700.0), heading("Usage", 2, 650.0)]);
+        assert_eq!(lines.len(), 2);
+    }
+}
@@ -1,8 +1,21 @@
 pub mod common;
 pub mod compact_lines;
+pub mod detect_captions;
 pub mod detect_code_blocks;
 pub mod detect_headers;
+pub mod detect_lists;
+pub mod detect_ocr_pages;
+pub mod detect_tables;
 pub mod detect_toc;
+pub mod detect_vertical_writing;
+pub mod generate_toc;
+pub mod link_toc;
+pub mod merge_headings;
+pub mod normalize_headings;
+pub mod normalize_text;
+pub mod remove_hidden_text;
 pub mod remove_repetitive_elements;
 pub mod stats;
+pub mod to_html;
 pub mod to_markdown;
+pub mod to_text;
@@ -0,0 +1,150 @@
+use crate::models::{BlockType, ItemType, ParseResult};
+use crate::transformations::common::Transformation;
+
+/// Rewrites heading levels into a consistent hierarchy. Levels are assigned per line by the
+/// detection stages, so a document can jump from H1 to H4 or open dozens of H1s.
+///
+/// - Each heading sits at most one level below the closest shallower heading before it.
+/// - Unless `multiple_h1` is set, only one H1 remains: a detected H1 that opens the document
+///   is kept as its title and every later top-level heading, with everything below it, moves
+///   down one level. Without such a title, all headings move down.
+/// - `heading_offset` then shifts all levels down and `max_level` caps them, for embedding the
+///   output into a larger site.
+pub struct NormalizeHeadings {
+    pub verbose: bool,
+    pub multiple_h1: bool,
+    pub heading_offset: usize,
+    /// Deepest level emitted (H6 if unset)
+    pub max_level: Option<usize>,
+}
+
+impl Transformation for NormalizeHeadings {
+    fn transform(&self, result: &mut ParseResult) {
+        let positions: Vec<(usize, usize, usize)> = result
+            .pages
+            .iter()
+            .enumerate()
+            .flat_map(|(page_idx, page)| {
+                page.items
+                    .iter()
+                    .enumerate()
+                    .filter_map(move |(item_idx, item)| match item {
+                        ItemType::LineItem(line) => line
+                            .block_type
+                            .heading_level()
+                            .map(|level| (page_idx, item_idx, level)),
+                        _ => None,
+                    })
+            })
+            .collect();
+
+        // Close the gaps: (original level, new level) of the open ancestors
+        let mut stack: Vec<(usize, usize)> = Vec::new();
+        let mut levels: Vec<usize> = Vec::with_capacity(positions.len());
+        for &(_, _, level) in &positions {
+            while stack.last().is_some_and(|&(open, _)| open >= level) {
+                stack.pop();
+            }
+            let normalized = stack.last().map_or(1, |&(_, parent)| parent + 1);
+            stack.push((level, normalized));
+            levels.push(normalized);
+        }
+
+        // A detected H1 that opens the document is its title: later top-level headings become
+        // siblings of its children. Without such a title, all top-level headings move down.
+        if !self.multiple_h1 {
+            if let Some(second_h1) = levels.iter().skip(1).position(|&l| l == 1) {
+                let has_title = positions.first().is_some_and(|&(_, _, level)| level == 1);
+                let start = if has_title { second_h1 + 1 } else { 0 };
+                for level in &mut levels[start..] {
+                    *level += 1;
+                }
+            }
+        }
+
+        let max_level = self.max_level.unwrap_or(6);
+        let mut changed = 0;
+        for (&(page_idx, item_idx, original), level) in positions.iter().zip(levels) {
+            let level = (level + self.heading_offset).min(max_level);
+            if level == original {
+                continue;
+            }
+            if let Some(ItemType::LineItem(line)) = result.pages[page_idx].items.get_mut(item_idx) {
+                line.block_type = BlockType::heading(level);
+                changed += 1;
+            }
+        }
+
+        if self.verbose {
+            crate::lgger!(
+                "NormalizeHeadings: Changed the level of {}/{} headings",
+                changed,
+                positions.len()
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{GlobalStats, LineItem, Page};
+
+    fn normalize(
+        levels: &[usize],
+        multiple_h1: bool,
+        offset: usize,
+        max: Option<usize>,
+    ) -> Vec<usize> {
+        let items = levels
+            .iter()
+            .map(|&level| {
+                ItemType::LineItem(LineItem {
+                    block_type: BlockType::heading(level),
+                    ..Default::default()
+                })
+            })
+            .collect();
+        let mut result = ParseResult {
+            pages: vec![Page {
+                items,
+                ..Default::default()
+            }],
+            globals: GlobalStats::default(),
+        };
+        NormalizeHeadings {
+            verbose: false,
+            multiple_h1,
+            heading_offset: offset,
+            max_level: max,
+        }
+        .transform(&mut result);
+        result.pages[0]
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                ItemType::LineItem(line) => line.block_type.heading_level(),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn closes_gaps_in_the_hierarchy() {
+        assert_eq!(normalize(&[1, 4, 2, 5], false, 0, None), [1, 2, 2, 3]);
+    }
+
+    #[test]
+    fn keeps_one_title_h1() {
+        // The opening H1 is the title; the next top-level heading moves below it
+        assert_eq!(normalize(&[1, 2, 1, 2], false, 0, None), [1, 2, 2, 3]);
+        // Without a title, every top-level heading moves down
+        assert_eq!(normalize(&[2, 1, 2, 1], false, 0, None), [2, 2, 3, 2]);
+        assert_eq!(normalize(&[1, 2, 1, 2], true, 0, None), [1, 2, 1, 2]);
+    }
+
+    #[test]
+    fn applies_offset_and_maximum_level() {
+        assert_eq!(normalize(&[1, 2, 3], false, 1, Some(3)), [2, 3, 3]);
+    }
+}
@@ -0,0 +1,264 @@
+use crate::models::{ItemType, ParseResult, TextItem};
+use crate::transformations::common::Transformation;
+use std::collections::BTreeSet;
+use unicode_normalization::UnicodeNormalization;
+
+/// Cleans up extracted text so the output can be searched:
+///
+/// - ligatures (`ﬁ`, `ﬂ`, ...) are spelled out;
+/// - soft hyphens are dropped, except at the end of a line, where they become a plain hyphen
+///   so the line is dehyphenated when it is joined with the next one;
+/// - non-breaking and fixed-width spaces become plain spaces, zero-width ones are dropped;
+/// - bullets from symbol fonts' private-use code points are mapped to Unicode;
+/// - a spacing accent drawn before its letter, as some TeX fonts do, becomes a combining mark;
+/// - the text is brought into Unicode normalization form C, so decomposed accents, kana with
+///   separate voicing marks and Hangul jamo compare equal to their precomposed forms.
+///
+/// Glyphs left without a Unicode value, because their font has no ToUnicode map, come out as
+/// control characters, U+FFFD or other private-use code points. They are dropped and counted
+/// per page in `Page::unmapped_glyphs`.
+pub struct NormalizeText {
+    pub verbose: bool,
+}
+
+impl Transformation for NormalizeText {
+    fn transform(&self, result: &mut ParseResult) {
+        for page in &mut result.pages {
+            let mut unmapped = 0;
+            let mut fonts = BTreeSet::new();
+
+            let mut items = page.items.iter_mut().filter_map(|item| match item {
+                ItemType::TextItem(text_item) => Some(text_item),
+                _ => None,
+            });
+            let mut current = items.next();
+            while let Some(item) = current {
+                let next = items.next();
+                let line_end = next.as_ref().is_none_or(|next| !same_line(item, next));
+                let (text, dropped) = normalize(&item.text, line_end);
+                if dropped > 0 {
+                    unmapped += dropped;
+                    fonts.insert(item.font.clone());
+                }
+                item.text = text;
+                current = next;
+            }
+            page.items.retain(|item| match item {
+                ItemType::TextItem(text_item) => !text_item.text.is_empty(),
+                _ => true,
+            });
+
+            page.unmapped_glyphs = unmapped;
+            if self.verbose && unmapped > 0 {
+                crate::lgger!(
+                    "NormalizeText: page {}: {} unmapped glyphs in {:?}",
+                    page.index + 1,
+                    unmapped,
+                    fonts
+                );
+            }
+        }
+    }
+}
+
+/// Whether `next` continues `item` on the same line, i.e. a soft hyphen at the end of `item`
+/// is not a line break.
+fn same_line(item: &TextItem, next: &TextItem) -> bool {
+    (item.y - next.y).abs() < item.font_size.max(1.0) * 0.5 && next.x >= item.x
+}
+
+/// Normalizes one text item. Returns the text and the number of unmapped glyphs dropped.
+fn normalize(text: &str, line_end: bool) -> (String, usize) {
+    let last_visible = text.trim_end().char_indices().last().map(|(i, _)| i);
+    let mut unmapped = 0;
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\u{AD}' => {
+                // The only place a soft hyphen is seen: where the line was broken
+                if line_end && Some(i) == last_visible {
+                    out.push('-');
+                }
+            }
+            '\u{A0}' | '\u{2000}'..='\u{200A}' | '\u{202F}' | '\u{205F}' => out.push(' '),
+            '\u{200B}' | '\u{2060}' | '\u{FEFF}' => {}
+            '\u{2011}' => out.push('-'),
+            '\u{FB00}'..='\u{FB06}' => out.push_str(expand_ligature(c)),
+            _ if is_unmapped(c) => match symbol_glyph(c) {
+                Some(symbol) => out.push(symbol),
+                None => unmapped += 1,
+            },
+            _ => match spacing_accent(c) {
+                // "´e": the accent is drawn first and placed over the letter
+                Some(mark) if chars.peek().is_some_and(|&(_, next)| composes(next, mark)) => {
+                    if let Some((_, letter)) = chars.next() {
+                        out.push(letter);
+                        out.push(mark);
+                    }
+                }
+                _ => out.push(c),
+            },
+        }
+    }
+
+    (out.nfc().collect(), unmapped)
+}
+
+fn expand_ligature(c: char) -> &'static str {
+    match c {
+        '\u{FB00}' => "ff",
+        '\u{FB01}' => "fi",
+        '\u{FB02}' => "fl",
+        '\u{FB03}' => "ffi",
+        '\u{FB04}' => "ffl",
+        _ => "st",
+    }
+}
+
+/// Code points pdfium reports for glyphs it could not map to Unicode.
+fn is_unmapped(c: char) -> bool {
+    (c.is_control() && !matches!(c, '\t' | '\n' | '\r'))
+        || matches!(
+            c,
+            '\u{FFFD}' | '\u{E000}'..='\u{F8FF}' | '\u{F0000}'..='\u{FFFFD}' | '\u{100000}'..='\u{10FFFD}'
+        )
+}
+
+/// Symbol and Wingdings fonts map their glyphs to U+F0xx; the common bullets are kept.
+fn symbol_glyph(c: char) -> Option<char> {
+    match c {
+        '\u{F0B7}' | '\u{F06C}' => Some('•'),
+        '\u{F0A7}' | '\u{F06E}' => Some('▪'),
+        '\u{F0D8}' => Some('➢'),
+        '\u{F0FC}' => Some('✓'),
+        '\u{F02D}' => Some('-'),
+        _ => None,
+    }
+}
+
+/// The combining mark of a spacing accent.
+fn spacing_accent(c: char) -> Option<char> {
+    Some(match c {
+        '\u{A8}' => '\u{308}',
+        '\u{AF}' => '\u{304}',
+        '\u{B4}' => '\u{301}',
+        '\u{B8}' => '\u{327}',
+        '\u{2C6}' => '\u{302}',
+        '\u{2C7}' => '\u{30C}',
+        '\u{2D8}' => '\u{306}',
+        '\u{2D9}' => '\u{307}',
+        '\u{2DA}' => '\u{30A}',
+        '\u{2DB}' => '\u{328}',
+        '\u{2DC}' => '\u{303}',
+        '\u{2DD}' => '\u{30B}',
+        _ => return None,
+    })
+}
+
+/// Whether `base` followed by the combining `mark` has a precomposed form.
+fn composes(base: char, mark: char) -> bool {
+    unicode_normalization::char::compose(base, mark).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{GlobalStats, Page};
+
+    fn item(text: &str, x: f64, y: f64) -> ItemType {
+        ItemType::TextItem(TextItem {
+            text: text.to_string(),
+            x,
+            y,
+            width: 40.0,
+            height: 10.0,
+            font: "F1".to_string(),
+            font_size: 10.0,
+            ..Default::default()
+        })
+    }
+
+    fn texts(page: &Page) -> Vec<&str> {
+        page.items
+            .iter()
+            .filter_map(|item| match item {
+                ItemType::TextItem(text_item) => Some(text_item.text.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn composes_combining_marks() {
+        assert_eq!(normalize("cafe\u{301}", false).0, "café");
+        assert_eq!(normalize("Vie\u{323}\u{302}t", false).0, "Việt");
+        // Marks out of canonical order are reordered before composing
+        assert_eq!(normalize("Vie\u{302}\u{323}t", false).0, "Việt");
+        assert_eq!(normalize("\u{438}\u{306}", false).0, "й");
+        assert_eq!(normalize("か\u{3099}", false).0, "が");
+        assert_eq!(normalize("\u{1112}\u{1161}\u{11AB}", false).0, "한");
+    }
+
+    #[test]
+    fn composes_spacing_accents_drawn_before_the_letter() {
+        assert_eq!(normalize("caf\u{B4}e", false).0, "café");
+        assert_eq!(normalize("\u{A8}uber", false).0, "über");
+        // Nothing to compose with: the accent stays
+        assert_eq!(normalize("\u{B4} x", false).0, "\u{B4} x");
+    }
+
+    #[test]
+    fn expands_ligatures() {
+        assert_eq!(
+            normalize("\u{FB01}nance e\u{FB00}ect", false).0,
+            "finance effect"
+        );
+        assert_eq!(
+            normalize("\u{FB03}x \u{FB04}y \u{FB02}z", false).0,
+            "ffix ffly flz"
+        );
+    }
+
+    #[test]
+    fn cleans_up_spaces_and_soft_hyphens() {
+        assert_eq!(normalize("10\u{A0}kg\u{200B}", false).0, "10 kg");
+        assert_eq!(normalize("hy\u{AD}phen", true).0, "hyphen");
+        assert_eq!(normalize("hy\u{AD}", true).0, "hy-");
+        assert_eq!(normalize("hy\u{AD} ", true).0, "hy- ");
+        assert_eq!(normalize("hy\u{AD}", false).0, "hy");
+    }
+
+    #[test]
+    fn drops_and_counts_unmapped_glyphs() {
+        assert_eq!(
+            normalize("\u{1}a\u{FFFD}b\u{E123}", false),
+            ("ab".to_string(), 3)
+        );
+        assert_eq!(normalize("\u{F0B7} item", false), ("• item".to_string(), 0));
+        assert_eq!(normalize("tab\tkept", false), ("tab\tkept".to_string(), 0));
+    }
+
+    #[test]
+    fn soft_hyphen_becomes_a_hyphen_only_at_the_end_of_a_line() {
+        let mut result = ParseResult {
+            pages: vec![Page {
+                items: vec![
+                    item("con\u{AD}", 10.0, 700.0),
+                    item("tinued", 50.0, 700.0),
+                    item("wrap\u{AD}", 10.0, 700.0),
+                    item("ped", 10.0, 686.0),
+                    item("\u{2}\u{3}", 10.0, 672.0),
+                ],
+                ..Default::default()
+            }],
+            globals: GlobalStats::default(),
+        };
+        NormalizeText { verbose: false }.transform(&mut result);
+
+        let page = &result.pages[0];
+        assert_eq!(texts(page), ["con", "tinued", "wrap-", "ped"]);
+        assert_eq!(page.unmapped_glyphs, 2);
+    }
+}
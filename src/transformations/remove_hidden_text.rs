@@ -0,0 +1,138 @@
+use crate::models::{BoundingBox, Color, ItemType, ParseResult, RenderMode, TextItem};
+use crate::transformations::common::Transformation;
+use std::collections::{HashMap, HashSet};
+
+/// Drops text a reader never sees before any statistics are taken:
+///
+/// - invisible text (render mode 3), except on pages `DetectOcrPages` marked as OCR layers;
+/// - text painted fully transparent, or white outside images and shaded shapes, i.e. in the
+///   color of the page background;
+/// - watermarks: light grey or translucent text that is much larger than body text or that
+///   repeats on many pages.
+pub struct RemoveHiddenText {
+    pub verbose: bool,
+}
+
+impl Transformation for RemoveHiddenText {
+    fn transform(&self, result: &mut ParseResult) {
+        let body_size = body_font_size(result);
+
+        // Faint text repeated on many pages is a watermark even at body size
+        let mut faint_pages: HashMap<String, HashSet<usize>> = HashMap::new();
+        for (page_idx, page) in result.pages.iter().enumerate() {
+            for item in &page.items {
+                if let ItemType::TextItem(text_item) = item {
+                    if is_faint(text_item) {
+                        faint_pages
+                            .entry(text_item.text.trim().to_string())
+                            .or_default()
+                            .insert(page_idx);
+                    }
+                }
+            }
+        }
+        let min_repeats = (result.pages.len() / 2).max(3);
+        let repeated: HashSet<String> = faint_pages
+            .into_iter()
+            .filter(|(_, pages)| pages.len() >= min_repeats)
+            .map(|(text, _)| text)
+            .collect();
+
+        let mut invisible = 0;
+        let mut background = 0;
+        let mut watermarks = 0;
+        for page in &mut result.pages {
+            let covered: Vec<BoundingBox> =
+                page.images.iter().chain(&page.shaded).copied().collect();
+            let ocr_layer = page.ocr;
+
+            page.items.retain(|item| {
+                let ItemType::TextItem(text_item) = item else {
+                    return true;
+                };
+                if text_item.render_mode == RenderMode::Invisible {
+                    invisible += usize::from(!ocr_layer);
+                    return ocr_layer;
+                }
+                if matches_background(text_item, &covered) {
+                    background += 1;
+                    return false;
+                }
+                let watermark = is_faint(text_item)
+                    && (text_item.font_size > body_size * 2.0
+                        || repeated.contains(text_item.text.trim()));
+                watermarks += usize::from(watermark);
+                !watermark
+            });
+        }
+
+        if self.verbose {
+            crate::lgger!(
+                "RemoveHiddenText: Removed {} invisible, {} background-colored and {} watermark text objects",
+                invisible,
+                background,
+                watermarks
+            );
+        }
+    }
+}
+
+/// Colors the text is painted with, as far as pdfium reported them.
+fn painted_colors(item: &TextItem) -> Vec<Color> {
+    let fill = item
+        .fill_color
+        .filter(|_| item.render_mode != RenderMode::Stroke);
+    let stroke = item.stroke_color.filter(|_| {
+        matches!(
+            item.render_mode,
+            RenderMode::Stroke | RenderMode::FillStroke
+        )
+    });
+    fill.into_iter().chain(stroke).collect()
+}
+
+/// Fully transparent, or white on (assumed) white paper rather than on an image or shape.
+fn matches_background(item: &TextItem, covered: &[BoundingBox]) -> bool {
+    let colors = painted_colors(item);
+    if colors.is_empty()
+        || colors
+            .iter()
+            .any(|color| color.a > 0 && color.lightness() <= 0.97)
+    {
+        return false;
+    }
+    let (x, y) = (item.x + item.width / 2.0, item.y - item.height / 2.0);
+    colors.iter().all(|color| color.a == 0) || !covered.iter().any(|region| region.contains(x, y))
+}
+
+/// Light grey or translucent, as watermarks are drawn. White is left to `matches_background`,
+/// since white text on a dark shape is plainly visible.
+fn is_faint(item: &TextItem) -> bool {
+    let colors = painted_colors(item);
+    !colors.is_empty()
+        && colors.iter().all(|color| {
+            color.a < 128
+                || (color.lightness() > 0.7 && color.lightness() <= 0.97 && color.saturation() < 32)
+        })
+}
+
+/// Most common font size by character count, ignoring hidden and faint text.
+fn body_font_size(result: &ParseResult) -> f64 {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for page in &result.pages {
+        for item in &page.items {
+            if let ItemType::TextItem(text_item) = item {
+                if text_item.render_mode != RenderMode::Invisible && !is_faint(text_item) {
+                    *counts
+                        .entry(format!("{:.1}", text_item.font_size))
+                        .or_insert(0) += text_item.text.len();
+                }
+            }
+        }
+    }
+    counts
+        .into_iter()
+        .max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(&a.0)))
+        .and_then(|(size, _)| size.parse().ok())
+        .unwrap_or(f64::MAX)
+}
@@ -181,7 +181,7 @@ fn hash_string(s: &str) -> u64 {
     // Normalize: remove digits, spaces, lowercase
     let normalized: String = s
         .chars()
-        .filter(|c| !c.is_digit(10) && !c.is_whitespace())
+        .filter(|c| !c.is_ascii_digit() && !c.is_whitespace())
         .flat_map(|c| c.to_lowercase())
         .collect();
 
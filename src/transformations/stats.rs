@@ -1,4 +1,6 @@
-use crate::models::{GlobalStats, Page, ParseResult, TextItem, WordFormat};
+use crate::models::{
+    FontProperties, GlobalStats, ItemType, Page, ParseResult, TextItem, WordFormat,
+};
 use crate::transformations::common::Transformation;
 use std::collections::HashMap;
 
@@ -12,17 +14,70 @@ impl Transformation for CalculateGlobalStats {
             crate::lgger!("Calculating global stats...");
         }
         result.globals = calculate_global_stats(&result.pages);
+        if self.verbose {
+            log_font_report(&result.pages, &result.globals);
+        }
+    }
+}
+
+/// Bold/italic/monospace style of a font. Taken from the font descriptor when pdfium reports
+/// one, and guessed from the font name otherwise.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct FontStyle {
+    bold: bool,
+    italic: bool,
+    monospace: bool,
+}
+
+fn font_style(name: &str, properties: &FontProperties) -> FontStyle {
+    if let Some(weight) = properties.weight {
+        return FontStyle {
+            bold: weight >= 600 || properties.force_bold,
+            italic: properties.italic || properties.italic_angle != 0,
+            monospace: properties.fixed_pitch,
+        };
+    }
+
+    // "ABCDEF+Minion-BoldIt": only the style part after the family name is meaningful
+    let base = name.split_once('+').map_or(name, |(_, base)| base);
+    let lower_base = base.to_lowercase();
+    // Short forms ("-Bd", "-BoldIt") are only trusted in a separate style suffix
+    let (style, suffixed) = match base.rsplit_once(['-', ',']) {
+        Some((_, style)) => (style.to_lowercase(), true),
+        None => (lower_base.clone(), false),
+    };
+    FontStyle {
+        bold: style.contains("bold") || style.contains("black") || (suffixed && style == "bd"),
+        italic: style.contains("italic")
+            || style.contains("oblique")
+            || (suffixed && (style.contains("ital") || style.ends_with("it"))),
+        monospace: lower_base.contains("mono")
+            || lower_base.contains("courier")
+            || lower_base.contains("consolas"),
     }
 }
 
 fn calculate_global_stats(pages: &[Page]) -> GlobalStats {
+    // OCR layers are sized to the scan rather than set in the document's fonts, so they only
+    // count when there is no born-digital text to measure
+    let digital: Vec<&Page> = pages
+        .iter()
+        .filter(|page| !page.ocr && page_has_text(page))
+        .collect();
+    let pages: Vec<&Page> = if digital.is_empty() {
+        pages.iter().collect()
+    } else {
+        digital
+    };
+
     let mut height_counts: HashMap<String, usize> = HashMap::new();
     let mut font_counts: HashMap<String, usize> = HashMap::new();
+    let mut font_styles: HashMap<String, FontStyle> = HashMap::new();
     let mut max_height = 0.0;
     let mut max_height_font = String::new();
 
     // 1. Collect height and font statistics
-    for page in pages {
+    for page in &pages {
         for item in &page.items {
             if let crate::models::ItemType::TextItem(text_item) = item {
                 let lower_font = text_item.font.to_lowercase();
@@ -41,11 +96,10 @@ fn calculate_global_stats(pages: &[Page]) -> GlobalStats {
                 // Weight by character count to ensure true body font wins.
                 // Penalty for bold/italic to prefer Regular as the "Body" baseline.
                 let mut weight = alpha_count;
-                let lower_font = text_item.font.to_lowercase();
-                if lower_font.contains("italic")
-                    || lower_font.contains("oblique")
-                    || lower_font.contains("bold")
-                {
+                let style = *font_styles
+                    .entry(text_item.font.clone())
+                    .or_insert_with(|| font_style(&text_item.font, &text_item.font_properties));
+                if style.bold || style.italic {
                     weight /= 10; // Significant penalty to prefer Regular
                 }
 
@@ -68,7 +122,7 @@ fn calculate_global_stats(pages: &[Page]) -> GlobalStats {
     // 2. Calculate most used distance
     let mut distance_counts: HashMap<String, usize> = HashMap::new();
 
-    for page in pages {
+    for page in &pages {
         let mut last_item_of_most_used_height: Option<&TextItem> = None;
 
         for item in &page.items {
@@ -101,21 +155,18 @@ fn calculate_global_stats(pages: &[Page]) -> GlobalStats {
     // 3. Map fonts to formats
     let mut font_to_format = HashMap::new();
     for font_name in font_counts.keys() {
-        let lower_name = font_name.to_lowercase();
-        let is_bold = lower_name.contains("bold") || lower_name.contains("-bd");
-        let is_italic = lower_name.contains("oblique")
-            || lower_name.contains("italic")
-            || lower_name.contains("-ital")
-            || lower_name.contains("-it");
+        let style = font_styles.get(font_name).copied().unwrap_or_default();
         let is_max_height_font = *font_name == max_height_font;
 
         let format = if *font_name == most_used_font {
             None
-        } else if is_bold && is_italic {
+        } else if style.monospace {
+            Some(WordFormat::Code)
+        } else if style.bold && style.italic {
             Some(WordFormat::BoldItalic)
-        } else if is_bold || is_max_height_font {
+        } else if style.bold || is_max_height_font {
             Some(WordFormat::Bold)
-        } else if is_italic {
+        } else if style.italic {
             Some(WordFormat::Italic)
         } else {
             None
@@ -135,6 +186,13 @@ fn calculate_global_stats(pages: &[Page]) -> GlobalStats {
     }
 }
 
+fn page_has_text(page: &Page) -> bool {
+    page.items.iter().any(|item| match item {
+        ItemType::TextItem(text_item) => !text_item.text.trim().is_empty(),
+        _ => false,
+    })
+}
+
 fn get_most_used_key(map: &HashMap<String, usize>) -> Option<String> {
     map.iter()
         .max_by_key(|entry| entry.1)
@@ -144,3 +202,60 @@ fn get_most_used_key(map: &HashMap<String, usize>) -> Option<String> {
 fn get_most_used_key_as_f64(map: &HashMap<String, usize>) -> Option<f64> {
     get_most_used_key(map).and_then(|s| s.parse::<f64>().ok())
 }
+
+/// Logs every font of the document with its usage, descriptor values and assigned format.
+fn log_font_report(pages: &[Page], globals: &GlobalStats) {
+    let mut fonts: HashMap<&str, (usize, f64, &FontProperties)> = HashMap::new();
+    for page in pages {
+        for item in &page.items {
+            if let ItemType::TextItem(text_item) = item {
+                let entry = fonts.entry(text_item.font.as_str()).or_insert((
+                    0,
+                    0.0,
+                    &text_item.font_properties,
+                ));
+                entry.0 += text_item
+                    .text
+                    .chars()
+                    .filter(|c| !c.is_whitespace())
+                    .count();
+                entry.1 = f64::max(entry.1, text_item.font_size);
+            }
+        }
+    }
+
+    let mut fonts: Vec<_> = fonts.into_iter().collect();
+    fonts.sort_by(|a, b| b.1 .0.cmp(&a.1 .0).then(a.0.cmp(b.0)));
+
+    crate::lgger!("Font report ({} fonts):", fonts.len());
+    for (name, (chars, max_size, properties)) in fonts {
+        let style = font_style(name, properties);
+        let role = if name == globals.most_used_font {
+            "body".to_string()
+        } else {
+            globals
+                .font_to_format
+                .get(name)
+                .map_or("-".to_string(), |format| format!("{:?}", format))
+        };
+        crate::lgger!(
+            "  {}: {} chars, max size {:.1}, weight {}, italic angle {}, bold={} italic={} monospace={} ({}) -> {}",
+            name,
+            chars,
+            max_size,
+            properties
+                .weight
+                .map_or("?".to_string(), |weight| weight.to_string()),
+            properties.italic_angle,
+            style.bold,
+            style.italic,
+            style.monospace,
+            if properties.weight.is_some() {
+                "descriptor"
+            } else {
+                "name"
+            },
+            role
+        );
+    }
+}
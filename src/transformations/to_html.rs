@@ -0,0 +1,571 @@
+use crate::models::{BlockType, ItemType, LineItem, ParseResult, TextItem, TocList, WordFormat};
+use crate::transformations::common::{
+    caption_label, cjk_boundary, clean_line_text, ends_sentence, join_line_items,
+    join_line_items_with, normalize_caption, scanned_placeholder, strip_list_marker,
+    strip_trailing_hyphen, unique_slug, wrap_trimmed, Transformation, LOW_CONFIDENCE_NOTE,
+};
+use std::collections::HashMap;
+
+pub struct ToHtml {
+    pub verbose: bool,
+    pub page_anchors: bool,
+}
+
+/// Block element that is still open at the end of the previous line.
+#[derive(Clone, Copy, PartialEq)]
+enum OpenBlock {
+    None,
+    Paragraph,
+    Footnote,
+    Code,
+    List { ordered: bool },
+    Caption,
+    Toc { depth: usize, list: TocList },
+    Table { columns: usize },
+}
+
+/// Writer state carried across pages so paragraphs, lists and code can span page breaks.
+struct HtmlWriter {
+    open: OpenBlock,
+    /// Plain text of the last line, without markup, for sentence ends and CJK joins
+    last_text: String,
+    last_hyphenated: bool,
+    last_y: Option<f64>,
+    slugs: HashMap<String, usize>,
+    pending_anchor: Option<u16>,
+    /// Direction of the open paragraph; a line in the other direction starts a new one
+    rtl: bool,
+}
+
+impl Transformation for ToHtml {
+    fn transform(&self, result: &mut ParseResult) {
+        let paragraph_gap = result.globals.most_used_distance * 1.1;
+        let total = result.pages.len();
+
+        let mut writer = HtmlWriter {
+            open: OpenBlock::None,
+            last_text: String::new(),
+            last_hyphenated: false,
+            last_y: None,
+            slugs: HashMap::new(),
+            pending_anchor: None,
+            rtl: false,
+        };
+
+        for (counter, page) in result.pages.iter_mut().enumerate() {
+            if self.verbose && ((counter + 1) % 50 == 0 || counter + 1 == total) {
+                crate::lgger!("ToHtml: Processed {}/{} pages...", counter + 1, total);
+            }
+
+            let mut html = String::new();
+            if self.page_anchors {
+                writer.pending_anchor = Some(page.index);
+            }
+            let new_page = writer.last_y.is_some();
+            writer.last_y = None;
+
+            let has_lines = page
+                .items
+                .iter()
+                .any(|item| matches!(item, ItemType::LineItem(_)));
+            if page.low_confidence && has_lines {
+                writer.close(&mut html);
+                html.push_str(&format!("<!-- {} -->\n", LOW_CONFIDENCE_NOTE));
+            }
+            if page.scanned && !has_lines {
+                writer.close(&mut html);
+                html.push_str("<p class=\"scanned-page\">");
+                writer.write_anchor(&mut html);
+                html.push_str(&format!(
+                    "[{}]</p>\n",
+                    escape_html(&scanned_placeholder(page.index))
+                ));
+            }
+
+            for item in &page.items {
+                let ItemType::LineItem(line) = item else {
+                    continue;
+                };
+
+                // Same rule as the Markdown page join: a line continues the open block if it is
+                // close to the previous one, or starts a page after an unfinished sentence.
+                let continues = match writer.last_y {
+                    Some(last_y) => (last_y - line.y).abs() <= paragraph_gap,
+                    None => new_page && !ends_sentence(&writer.last_text),
+                };
+                writer.last_y = Some(line.y);
+
+                let merged = join_line_items(line);
+
+                if let Some(level) = line.block_type.heading_level() {
+                    writer.close(&mut html);
+                    let text = clean_line_text(merged);
+                    let id = unique_slug(&mut writer.slugs, &text);
+                    html.push_str(&format!(
+                        "<h{} id=\"{}\"{}>",
+                        level,
+                        escape_html(&id),
+                        dir_attribute(line)
+                    ));
+                    writer.write_anchor(&mut html);
+                    html.push_str(&format!("{}</h{}>\n", escape_html(&text), level));
+                    writer.last_text = text;
+                    continue;
+                }
+
+                match line.block_type {
+                    BlockType::Code => {
+                        let text = merged;
+                        if writer.open == OpenBlock::Code {
+                            html.push('\n');
+                        } else {
+                            writer.close(&mut html);
+                            html.push_str("<pre><code>");
+                            writer.open = OpenBlock::Code;
+                        }
+                        writer.write_anchor(&mut html);
+                        html.push_str(&escape_html(&text));
+                        writer.last_text = text;
+                    }
+                    BlockType::TocItem(level) => {
+                        let text = merged.split_whitespace().collect::<Vec<_>>().join(" ");
+                        let target = line.toc_target.as_ref();
+                        let slug = target.and_then(|t| t.slug.as_deref());
+                        let list = target.map_or(TocList::Contents, |t| t.list);
+                        writer.write_toc_entry(&mut html, level, &text, slug, list);
+                        writer.last_text = text;
+                    }
+                    BlockType::Caption => {
+                        let text = clean_line_text(merged);
+                        // A label opens a new figure; lines without one continue the open caption
+                        let label = caption_label(&text);
+                        if label.is_some() || writer.open != OpenBlock::Caption {
+                            writer.close(&mut html);
+                            let class = match label.map(|l| l.list) {
+                                Some(TocList::Tables) => "table",
+                                _ => "figure",
+                            };
+                            html.push_str(&format!(
+                                "<figure class=\"{}\"{}>",
+                                class,
+                                dir_attribute(line)
+                            ));
+                            writer.write_anchor(&mut html);
+                            html.push_str("<figcaption>");
+                            writer.open = OpenBlock::Caption;
+                        } else {
+                            html.push('\n');
+                        }
+                        if let Some(anchor) = &line.anchor {
+                            html.push_str(&format!("<span id=\"{}\"></span>", escape_html(anchor)));
+                        }
+                        html.push_str(&escape_html(&normalize_caption(&text)));
+                        writer.last_text = text;
+                    }
+                    BlockType::TableRow => {
+                        // The first row of a table is its header row
+                        let columns = line.cells.len();
+                        let tag = if writer.open == (OpenBlock::Table { columns }) {
+                            "td"
+                        } else {
+                            writer.close(&mut html);
+                            html.push_str(&format!("<table{}>\n<thead>\n", dir_attribute(line)));
+                            writer.open = OpenBlock::Table { columns };
+                            "th"
+                        };
+                        html.push_str("<tr>");
+                        for (idx, cell) in line.cells.iter().enumerate() {
+                            html.push_str(&format!("<{}>", tag));
+                            if idx == 0 {
+                                writer.write_anchor(&mut html);
+                            }
+                            html.push_str(&format!("{}</{}>", escape_html(cell), tag));
+                        }
+                        html.push_str("</tr>\n");
+                        if tag == "th" {
+                            html.push_str("</thead>\n<tbody>\n");
+                        }
+                        writer.last_text = line.cells.join(" ");
+                    }
+                    BlockType::ListItem => {
+                        // The list element numbers or bullets the item; the marker is dropped
+                        let mut line = line.clone();
+                        let number = strip_list_marker(&mut line).and_then(|m| m.number);
+                        let text = clean_line_text(join_line_items_with(&line, html_emphasis));
+                        let ordered = number.is_some();
+                        if writer.open != (OpenBlock::List { ordered }) {
+                            writer.close(&mut html);
+                            html.push_str(&match number {
+                                Some(1) => "<ol>\n".to_string(),
+                                Some(start) => format!("<ol start=\"{}\">\n", start),
+                                None => "<ul>\n".to_string(),
+                            });
+                            writer.open = OpenBlock::List { ordered };
+                        }
+                        html.push_str(&format!("<li{}>", dir_attribute(&line)));
+                        writer.write_anchor(&mut html);
+                        html.push_str(&format!("{}</li>\n", text));
+                        writer.last_text = clean_line_text(join_line_items(&line));
+                    }
+                    _ => {
+                        let kind = if line.block_type == BlockType::Footnote {
+                            OpenBlock::Footnote
+                        } else {
+                            OpenBlock::Paragraph
+                        };
+                        let mut line = line.clone();
+                        let hyphenated = strip_trailing_hyphen(&mut line);
+                        let text = clean_line_text(join_line_items_with(&line, html_emphasis));
+
+                        if writer.open == kind && continues && writer.rtl == line.rtl {
+                            // A dropped trailing hyphen glues the next line on without a break,
+                            // as does CJK text, where the break would render as a space
+                            if !writer.last_hyphenated && !cjk_boundary(&writer.last_text, &merged)
+                            {
+                                html.push('\n');
+                            }
+                        } else {
+                            writer.close(&mut html);
+                            html.push_str(if kind == OpenBlock::Footnote {
+                                "<p class=\"footnote\""
+                            } else {
+                                "<p"
+                            });
+                            html.push_str(&format!("{}>", dir_attribute(&line)));
+                            writer.open = kind;
+                            writer.rtl = line.rtl;
+                        }
+                        writer.write_anchor(&mut html);
+                        if let Some(anchor) = &line.anchor {
+                            html.push_str(&format!("<span id=\"{}\"></span>", escape_html(anchor)));
+                        }
+                        html.push_str(&text);
+                        writer.last_text = clean_line_text(merged);
+                        writer.last_hyphenated = hyphenated;
+                    }
+                }
+            }
+
+            // Anchor for a page without any lines
+            if writer.pending_anchor.is_some() && writer.open == OpenBlock::None {
+                writer.write_anchor(&mut html);
+                html.push('\n');
+            }
+
+            page.items = vec![ItemType::Html(html)];
+        }
+
+        if let Some(ItemType::Html(html)) = result
+            .pages
+            .last_mut()
+            .and_then(|page| page.items.last_mut())
+        {
+            writer.close(html);
+        }
+    }
+}
+
+impl HtmlWriter {
+    fn close(&mut self, html: &mut String) {
+        match self.open {
+            OpenBlock::None => {}
+            OpenBlock::Paragraph | OpenBlock::Footnote => html.push_str("</p>\n"),
+            OpenBlock::Code => html.push_str("</code></pre>\n"),
+            OpenBlock::Caption => html.push_str("</figcaption></figure>\n"),
+            OpenBlock::List { ordered } => {
+                html.push_str(if ordered { "</ol>\n" } else { "</ul>\n" })
+            }
+            OpenBlock::Table { .. } => html.push_str("</tbody>\n</table>\n"),
+            OpenBlock::Toc { depth, .. } => {
+                html.push_str("</li>\n");
+                for _ in 0..depth {
+                    html.push_str("</ul>\n</li>\n");
+                }
+                html.push_str("</ul>\n</nav>\n");
+            }
+        }
+        self.open = OpenBlock::None;
+    }
+
+    fn write_anchor(&mut self, html: &mut String) {
+        if let Some(page) = self.pending_anchor.take() {
+            html.push_str(&format!(
+                "<span id=\"page-{}\" class=\"page-anchor\"></span>",
+                page + 1
+            ));
+        }
+    }
+
+    /// Emits a TOC entry, opening or closing nested lists to reach `level`. Lists of figures
+    /// and tables get their own `<nav>`.
+    fn write_toc_entry(
+        &mut self,
+        html: &mut String,
+        level: usize,
+        text: &str,
+        slug: Option<&str>,
+        list: TocList,
+    ) {
+        let (mut depth, mut li_open) = match self.open {
+            OpenBlock::Toc { depth, list: open } if open == list => (depth, true),
+            _ => {
+                self.close(html);
+                html.push_str(match list {
+                    TocList::Contents => "<nav class=\"toc\">\n<ul>\n",
+                    TocList::Figures => "<nav class=\"toc toc-figures\">\n<ul>\n",
+                    TocList::Tables => "<nav class=\"toc toc-tables\">\n<ul>\n",
+                });
+                (0, false)
+            }
+        };
+
+        if level > depth {
+            // Nested lists live inside the previous entry's <li>
+            while depth < level {
+                if !li_open {
+                    html.push_str("<li>");
+                }
+                html.push_str("\n<ul>\n");
+                depth += 1;
+                li_open = false;
+            }
+        } else if li_open {
+            html.push_str("</li>\n");
+            while depth > level {
+                html.push_str("</ul>\n</li>\n");
+                depth -= 1;
+            }
+        }
+
+        html.push_str("<li>");
+        self.write_anchor(html);
+        match slug {
+            Some(slug) => html.push_str(&format!(
+                "<a href=\"#{}\">{}</a>",
+                escape_html(slug),
+                escape_html(text)
+            )),
+            None => html.push_str(&escape_html(text)),
+        }
+        self.open = OpenBlock::Toc { depth, list };
+    }
+}
+
+/// Wraps the rendered body into a standalone HTML document with a minimal stylesheet.
+pub fn html_document(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+         <title>{}</title>\n<style>\n{}</style>\n</head>\n<body>\n<main>\n{}</main>\n</body>\n</html>\n",
+        escape_html(title),
+        STYLESHEET,
+        body
+    )
+}
+
+const STYLESHEET: &str = "body { font-family: system-ui, sans-serif; line-height: 1.6; margin: 0; }
+main { max-width: 50rem; margin: 0 auto; padding: 2rem 1rem; }
+h1, h2, h3, h4, h5, h6 { line-height: 1.25; margin: 1.5em 0 0.5em; }
+pre { background: #f5f5f5; padding: 0.75rem 1rem; overflow-x: auto; }
+code { font-family: ui-monospace, monospace; font-size: 0.9em; }
+nav.toc ul { list-style: none; padding-left: 1.25rem; }
+p.footnote { font-size: 0.85em; color: #555; }
+figure { margin: 1em 0; }
+figcaption { font-style: italic; }
+table { border-collapse: collapse; margin: 1em 0; }
+th, td { border: 1px solid #ddd; padding: 0.25rem 0.5rem; text-align: left; }
+p.scanned-page { color: #777; font-style: italic; }
+.page-anchor { display: inline; }
+";
+
+/// `dir` attribute for a block opened by `line`, marking right-to-left text.
+fn dir_attribute(line: &LineItem) -> &'static str {
+    if line.rtl {
+        " dir=\"rtl\""
+    } else {
+        ""
+    }
+}
+
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Escapes an item and wraps it in the tag matching its `format`, inside `<mark>` when the
+/// item is highlighted.
+fn html_emphasis(item: &TextItem) -> String {
+    let text = escape_html(&item.text);
+    let text = match item.format {
+        Some(WordFormat::Bold) => wrap_trimmed(&text, "<strong>", "</strong>"),
+        Some(WordFormat::Italic) => wrap_trimmed(&text, "<em>", "</em>"),
+        Some(WordFormat::BoldItalic) => wrap_trimmed(&text, "<strong><em>", "</em></strong>"),
+        _ => text,
+    };
+    if item.highlighted {
+        wrap_trimmed(&text, "<mark>", "</mark>")
+    } else {
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{GlobalStats, Page};
+
+    fn item(text: &str, y: f64) -> ItemType {
+        ItemType::LineItem(LineItem {
+            items: vec![TextItem {
+                text: text.to_string(),
+                y,
+                font_size: 10.0,
+                ..Default::default()
+            }],
+            y,
+            block_type: BlockType::ListItem,
+            ..Default::default()
+        })
+    }
+
+    fn row(cells: &[&str], y: f64) -> ItemType {
+        ItemType::LineItem(LineItem {
+            y,
+            block_type: BlockType::TableRow,
+            cells: cells.iter().map(|c| c.to_string()).collect(),
+            ..Default::default()
+        })
+    }
+
+    fn render(items: Vec<ItemType>) -> String {
+        let mut result = ParseResult {
+            pages: vec![Page {
+                items,
+                ..Default::default()
+            }],
+            globals: GlobalStats {
+                most_used_distance: 12.0,
+                ..Default::default()
+            },
+        };
+        ToHtml {
+            verbose: false,
+            page_anchors: false,
+        }
+        .transform(&mut result);
+        result.pages[0]
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                ItemType::Html(html) => Some(html.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn drops_list_markers_the_list_element_renders() {
+        let html = render(vec![item("1. First", 700.0), item("2. Second", 688.0)]);
+        assert!(
+            html.contains("<ol>\n<li>First</li>\n<li>Second</li>\n"),
+            "{}",
+            html
+        );
+
+        let html = render(vec![item("• First", 700.0), item("• Second", 688.0)]);
+        assert!(
+            html.contains("<ul>\n<li>First</li>\n<li>Second</li>\n"),
+            "{}",
+            html
+        );
+
+        let html = render(vec![item("3. Third", 700.0)]);
+        assert!(
+            html.contains("<ol start=\"3\">\n<li>Third</li>\n"),
+            "{}",
+            html
+        );
+    }
+
+    #[test]
+    fn renders_table_rows_with_a_header_row() {
+        let html = render(vec![
+            row(&["Name", "Size"], 700.0),
+            row(&["Width", "<12"], 686.0),
+            row(&["Depth", ""], 672.0),
+        ]);
+        assert_eq!(
+            html,
+            "<table>\n<thead>\n<tr><th>Name</th><th>Size</th></tr>\n</thead>\n<tbody>\n\
+             <tr><td>Width</td><td>&lt;12</td></tr>\n<tr><td>Depth</td><td></td></tr>\n\
+             </tbody>\n</table>\n"
+        );
+    }
+
+    #[test]
+    fn starts_a_new_paragraph_after_a_page_ending_in_markup() {
+        let paragraph = |text: &str, format| {
+            ItemType::LineItem(LineItem {
+                items: vec![TextItem {
+                    text: text.to_string(),
+                    y: 700.0,
+                    font_size: 10.0,
+                    format,
+                    ..Default::default()
+                }],
+                y: 700.0,
+                ..Default::default()
+            })
+        };
+        let mut result = ParseResult {
+            pages: vec![
+                Page {
+                    items: vec![paragraph("It is done.", Some(WordFormat::Italic))],
+                    ..Default::default()
+                },
+                Page {
+                    index: 1,
+                    items: vec![paragraph("Next \"topic.\"", None)],
+                    ..Default::default()
+                },
+                Page {
+                    index: 2,
+                    items: vec![paragraph("Last page.", None)],
+                    ..Default::default()
+                },
+            ],
+            globals: GlobalStats {
+                most_used_distance: 12.0,
+                ..Default::default()
+            },
+        };
+        ToHtml {
+            verbose: false,
+            page_anchors: false,
+        }
+        .transform(&mut result);
+        let html: String = result
+            .pages
+            .iter()
+            .flat_map(|page| &page.items)
+            .filter_map(|item| match item {
+                ItemType::Html(html) => Some(html.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            html,
+            "<p><em>It is done.</em></p>\n<p>Next &quot;topic.&quot;</p>\n<p>Last page.</p>\n"
+        );
+    }
+}
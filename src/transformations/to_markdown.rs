@@ -1,5 +1,12 @@
-use crate::models::{BlockType, ItemType, ParseResult};
-use crate::transformations::common::Transformation;
+use crate::models::{
+    BlockType, BoundingBox, Figure, ItemType, LineItem, ParseResult, SourceSpan, TextItem,
+    WordFormat,
+};
+use crate::transformations::common::{
+    caption_label, cjk_boundary, clean_line_text, join_line_items, join_line_items_with,
+    markdown_table_row, markdown_table_rule, normalize_caption, scanned_placeholder,
+    strip_list_marker, strip_trailing_hyphen, wrap_trimmed, Transformation, LOW_CONFIDENCE_NOTE,
+};
 
 pub struct ToMarkdown {
     pub verbose: bool,
@@ -24,13 +31,36 @@ impl Transformation for ToMarkdown {
             }
 
             let mut markdown = String::new();
+            let mut spans: Vec<SourceSpan> = Vec::new();
+            // Newlines in the first `counted` bytes of `markdown`, so span line numbers only
+            // scan the text pushed since the previous line
+            let mut lines = 0;
+            let mut counted = 0;
             // Removed explicit page separator here; handled in converter.rs
 
             let mut in_code_block = false;
             let mut last_y = -1.0;
             let mut last_was_header = false;
+            let mut last_was_toc = false;
+            let mut last_was_caption = false;
+            let mut last_was_paragraph = false;
+            let mut last_was_list = false;
+            // Column count of the table the previous line belongs to
+            let mut last_table: Option<usize> = None;
+            // Images top to bottom, each placed before the first line below its middle
+            let mut figures: Vec<&Figure> = page.figures.iter().collect();
+            figures.sort_by(|a, b| figure_middle(b).total_cmp(&figure_middle(a)));
+            let mut figures = figures.into_iter().peekable();
+
+            for (item_idx, item) in page.items.iter().enumerate() {
+                if let (ItemType::LineItem(line), false) = (item, in_code_block) {
+                    while let Some(figure) = figures.next_if(|f| figure_middle(f) > line.y) {
+                        push_figure(&mut markdown, figure);
+                        last_was_paragraph = false;
+                        last_table = None;
+                    }
+                }
 
-            for item in &page.items {
                 let mut is_code = false;
 
                 if let ItemType::LineItem(line) = item {
@@ -63,7 +93,7 @@ impl Transformation for ToMarkdown {
                                 .map(|i| i.text.as_str())
                                 .collect::<Vec<_>>()
                                 .join("");
-                            let trimmed = text.trim_matches(|c| c == '*' || c == '_').trim();
+                            let trimmed = text.trim();
                             if trimmed.to_lowercase().starts_with("chapter")
                                 || trimmed.to_lowercase().starts_with("part")
                                 || trimmed.to_lowercase().starts_with("appendix")
@@ -72,8 +102,36 @@ impl Transformation for ToMarkdown {
                             }
                         }
 
-                        if needs_break {
-                            markdown.push_str("\n");
+                        // A TOC is its own list; never glue it to surrounding text
+                        let is_toc = matches!(line.block_type, BlockType::TocItem(_));
+                        if is_toc != last_was_toc {
+                            needs_break = true;
+                        }
+
+                        // Likewise a list, which would otherwise swallow the next paragraph
+                        if (line.block_type == BlockType::ListItem) != last_was_list {
+                            needs_break = true;
+                        }
+
+                        // Captions are paragraphs of their own, as is each caption in a run
+                        if line.block_type == BlockType::Caption
+                            && (!last_was_caption || starts_caption(line))
+                        {
+                            needs_break = true;
+                        }
+                        if line.block_type != BlockType::Caption && last_was_caption {
+                            needs_break = true;
+                        }
+
+                        // A table is its own block, and so is each table that follows it
+                        let table =
+                            (line.block_type == BlockType::TableRow).then_some(line.cells.len());
+                        if table != last_table {
+                            needs_break = true;
+                        }
+
+                        if needs_break && !markdown.ends_with("\n\n") {
+                            markdown.push('\n');
                         }
                     }
 
@@ -88,13 +146,12 @@ impl Transformation for ToMarkdown {
                                 | BlockType::H5
                                 | BlockType::H6
                         )
+                        && !markdown.ends_with("\n\n")
                     {
-                        if !markdown.ends_with("\n\n") {
-                            if markdown.ends_with('\n') {
-                                markdown.push('\n');
-                            } else {
-                                markdown.push_str("\n\n");
-                            }
+                        if markdown.ends_with('\n') {
+                            markdown.push('\n');
+                        } else {
+                            markdown.push_str("\n\n");
                         }
                     }
 
@@ -113,172 +170,161 @@ impl Transformation for ToMarkdown {
 
                 match item {
                     ItemType::LineItem(line) => {
+                        let is_header = line.block_type.heading_level().is_some();
+                        let is_verbatim =
+                            matches!(line.block_type, BlockType::TocItem(_) | BlockType::Code);
+                        let is_caption = line.block_type == BlockType::Caption;
+
+                        // Headings, TOC items, code and captions are rendered without emphasis
+                        // markup; captions are italicized as a whole instead.
+                        let mut marker = None;
+                        let merged = if is_header || is_verbatim || is_caption {
+                            join_line_items(line)
+                        } else {
+                            let mut line = line.clone();
+                            if line.block_type == BlockType::ListItem {
+                                marker = strip_list_marker(&mut line);
+                            }
+                            strip_trailing_hyphen(&mut line);
+                            join_line_items_with(&line, markdown_emphasis)
+                        };
                         // For TOC items and Code, we want to preserve whitespace/indentation.
                         // For others, we normalize.
-                        let text = {
-                            let mut merged = String::new();
-                            if !line.items.is_empty() {
-                                let mut prev_item = &line.items[0];
-                                merged.push_str(&prev_item.text);
-                                for item in line.items.iter().skip(1) {
-                                    let gap = item.x - (prev_item.x + prev_item.width);
-                                    let glue_threshold = (prev_item.font_size * 0.2).max(3.0);
-                                    let starts_with_punct =
-                                        item.text.trim().starts_with(|c: char| {
-                                            matches!(
-                                                c,
-                                                ',' | '.' | ':' | ';' | ')' | ']' | '?' | '!'
-                                            )
-                                        });
-                                    let ends_with_open_punct =
-                                        merged.trim().ends_with(|c: char| matches!(c, '(' | '['));
-
-                                    if gap > glue_threshold
-                                        && !merged.ends_with(' ')
-                                        && !item.text.starts_with(' ')
-                                        && !starts_with_punct
-                                        && !ends_with_open_punct
-                                    {
-                                        merged.push(' ');
-                                    }
-                                    merged.push_str(&item.text);
-                                    prev_item = item;
-                                }
+                        let mut text = if is_verbatim {
+                            merged
+                        } else {
+                            clean_line_text(merged)
+                        };
+                        if is_caption {
+                            // "*Figure 3-2: Request flow*", opened on the label line and closed
+                            // on the last line of the caption
+                            if !last_was_caption || starts_caption(line) {
+                                text = format!("*{}", normalize_caption(&text));
                             }
+                            let continued = matches!(
+                                page.items.get(item_idx + 1),
+                                Some(ItemType::LineItem(next))
+                                    if next.block_type == BlockType::Caption
+                                        && !starts_caption(next)
+                            );
+                            if !continued {
+                                text.push('*');
+                            }
+                        }
+                        // Link target for lines such as captions that have no heading slug
+                        if let Some(anchor) = &line.anchor {
+                            text = format!("<a id=\"{}\"></a>{}", anchor, text);
+                        }
 
-                            if matches!(line.block_type, BlockType::TocItem(_) | BlockType::Code) {
-                                merged
-                            } else {
-                                // Implement hyphen removal: if a line ends with a hyphen, remove it.
-                                if merged.ends_with('-') {
-                                    merged.pop(); // Remove the hyphen
-                                }
+                        lines += markdown[counted..].matches('\n').count();
 
-                                // Fix range spacing: "66 - 68" -> "66-68"
-                                let cleaned =
-                                    merged.split_whitespace().collect::<Vec<_>>().join(" ");
-
-                                // Regex-like replacement for "number - number"
-                                // We'll do it simply with string replacement if it matches pattern
-                                // For indices specifically:
-                                let mut final_text = String::new();
-                                let tokens: Vec<&str> = cleaned.split(' ').collect();
-                                for (i, token) in tokens.iter().enumerate() {
-                                    if *token == "-" && i > 0 && i < tokens.len() - 1 {
-                                        let prev = tokens[i - 1];
-                                        let next = tokens[i + 1];
-                                        if prev.chars().all(|c| c.is_numeric())
-                                            && next.chars().all(|c| c.is_numeric())
-                                        {
-                                            if !final_text.is_empty() {
-                                                final_text.pop(); // Remove the space before '-'
-                                            }
-                                            final_text.push_str("-");
-                                            continue;
-                                        }
-                                    }
-                                    if i > 0 && !final_text.ends_with('-') {
-                                        final_text.push(' ');
-                                    }
-                                    final_text.push_str(token);
-                                }
-                                final_text
-                            }
-                        };
+                        // CJK lines wrap without spaces; a soft line break would render as one
+                        if line.block_type == BlockType::Paragraph
+                            && last_was_paragraph
+                            && markdown.ends_with('\n')
+                            && !markdown.ends_with("\n\n")
+                            && cjk_boundary(&markdown, &text)
+                        {
+                            markdown.pop();
+                            lines -= 1;
+                        }
 
-                        let is_header = matches!(
-                            line.block_type,
-                            BlockType::H1
-                                | BlockType::H2
-                                | BlockType::H3
-                                | BlockType::H4
-                                | BlockType::H5
-                                | BlockType::H6
-                        );
+                        let start_line = lines;
+                        let start = markdown.len();
 
                         match line.block_type {
-                            BlockType::H1 => {
-                                let clean = text.replace("**", "").replace("_", "");
-                                markdown.push_str(&format!("# {}\n\n", clean));
-                            }
-                            BlockType::H2 => {
-                                let clean = text.replace("**", "").replace("_", "");
-                                markdown.push_str(&format!("## {}\n\n", clean));
-                            }
-                            BlockType::H3 => {
-                                let clean = text.replace("**", "").replace("_", "");
-                                markdown.push_str(&format!("### {}\n\n", clean));
-                            }
-                            BlockType::H4 => {
-                                let clean = text.replace("**", "").replace("_", "");
-                                markdown.push_str(&format!("#### {}\n\n", clean));
+                            _ if is_header => {
+                                let level = line.block_type.heading_level().unwrap_or(6);
+                                markdown.push_str(&format!("{} {}\n\n", "#".repeat(level), text));
                             }
-                            BlockType::H5 => {
-                                let clean = text.replace("**", "").replace("_", "");
-                                markdown.push_str(&format!("##### {}\n\n", clean));
-                            }
-                            BlockType::H6 => {
-                                let clean = text.replace("**", "").replace("_", "");
-                                markdown.push_str(&format!("###### {}\n\n", clean));
-                            }
-                            BlockType::ListItem => markdown.push_str(&format!("- {}\n", text)),
+                            BlockType::ListItem => match marker.and_then(|m| m.number) {
+                                Some(number) => {
+                                    markdown.push_str(&format!("{}. {}\n", number, text))
+                                }
+                                None => markdown.push_str(&format!("- {}\n", text)),
+                            },
                             BlockType::TocItem(level) => {
-                                let clean = text.replace("**", "").replace("_", "");
-                                let trimmed = clean.trim();
                                 // Normalize spaces (e.g. "1.  First" -> "1. First")
                                 let normalized =
-                                    trimmed.split_whitespace().collect::<Vec<_>>().join(" ");
+                                    text.split_whitespace().collect::<Vec<_>>().join(" ");
 
                                 // Check if it starts with a number (e.g. "1.", "10.")
                                 let starts_with_number = normalized
                                     .split_whitespace()
                                     .next()
-                                    .map_or(false, |first_word| {
-                                        first_word.chars().all(|c| c.is_digit(10) || c == '.')
+                                    .is_some_and(|first_word| {
+                                        first_word.chars().all(|c| c.is_ascii_digit() || c == '.')
                                             && first_word.contains('.')
                                     });
 
+                                // Link the title to its heading when LinkTOC found one
+                                let slug = line.toc_target.as_ref().and_then(|t| t.slug.as_ref());
+                                let linked = |title: &str| match slug {
+                                    Some(slug) => format!("[{}](#{})", title, slug),
+                                    None => title.to_string(),
+                                };
+
                                 if starts_with_number {
                                     // Use the number as the list marker (e.g. "1. Title")
+                                    let (number, title) =
+                                        normalized.split_once(' ').unwrap_or((&normalized, ""));
+                                    let entry = if title.is_empty() {
+                                        linked(number)
+                                    } else {
+                                        format!("{} {}", number, linked(title))
+                                    };
                                     markdown.push_str(&format!(
                                         "{}{}\n",
                                         "   ".repeat(level),
-                                        normalized
+                                        entry
                                     ));
                                 } else {
                                     // Use dash as the list marker (e.g. "- Title")
                                     markdown.push_str(&format!(
                                         "{}- {}\n",
                                         "   ".repeat(level),
-                                        normalized
+                                        linked(&normalized)
                                     ));
                                 }
                             }
-                            BlockType::Code => {
-                                let mut clean_text =
-                                    text.trim_matches(|c| c == '*' || c == '_').to_string();
-                                // Also remove internal bold/italic markers if they wrap the whole line?
-                                // User request: "_My special thanks..._" -> "My special thanks..."
-                                // If the line starts and ends with _, remove them.
-                                if clean_text.starts_with('_') && clean_text.ends_with('_') {
-                                    clean_text = clean_text[1..clean_text.len() - 1].to_string();
+                            BlockType::TableRow => {
+                                markdown.push_str(&markdown_table_row(&line.cells));
+                                markdown.push('\n');
+                                // The first row doubles as the header row
+                                if last_table != Some(line.cells.len()) {
+                                    markdown.push_str(&markdown_table_rule(line.cells.len()));
+                                    markdown.push('\n');
                                 }
-
+                            }
+                            BlockType::Code => {
                                 // User request: "tabulate text to the right" inside code block
-                                if is_code {
-                                    markdown.push_str(&format!("\t{}\n", clean_text));
-                                } else {
-                                    markdown.push_str(&format!("\t{}\n", clean_text));
-                                }
+                                markdown.push_str(&format!("\t{}\n", text));
                             }
                             BlockType::Paragraph => markdown.push_str(&format!("{}\n", text)),
                             _ => markdown.push_str(&format!("{}\n", text)),
                         }
+
+                        lines += markdown[start..].matches('\n').count();
+                        counted = markdown.len();
+                        let trailing = markdown.len() - markdown.trim_end_matches('\n').len();
+                        let end_line = lines - trailing;
+                        record_span(&mut spans, line, page.index, start_line, end_line);
                         last_was_header = is_header;
+                        last_was_toc = matches!(line.block_type, BlockType::TocItem(_));
+                        last_was_caption = is_caption;
+                        last_was_paragraph = line.block_type == BlockType::Paragraph;
+                        last_was_list = line.block_type == BlockType::ListItem;
+                        last_table =
+                            (line.block_type == BlockType::TableRow).then_some(line.cells.len());
                     }
                     ItemType::TextItem(text_item) => {
                         markdown.push_str(&format!("{}\n", text_item.text));
                         last_was_header = false;
+                        last_was_toc = false;
+                        last_was_caption = false;
+                        last_was_paragraph = false;
+                        last_was_list = false;
+                        last_table = None;
                     }
                     _ => {}
                 }
@@ -287,8 +333,204 @@ impl Transformation for ToMarkdown {
             if in_code_block {
                 markdown.push_str("```\n\n");
             }
+            for figure in figures {
+                push_figure(&mut markdown, figure);
+            }
 
-            page.items = vec![ItemType::Markdown(markdown)];
+            if page.scanned && markdown.trim().is_empty() {
+                markdown = format!("*[{}]*\n\n", scanned_placeholder(page.index));
+            }
+
+            if page.low_confidence && !markdown.trim().is_empty() {
+                markdown.insert_str(0, &format!("<!-- {} -->\n\n", LOW_CONFIDENCE_NOTE));
+                for span in &mut spans {
+                    span.start_line += 2;
+                    span.end_line += 2;
+                }
+            }
+
+            page.items = vec![ItemType::Markdown(markdown), ItemType::SourceMap(spans)];
         }
     }
 }
+
+fn figure_middle(figure: &Figure) -> f64 {
+    figure.bbox.y - figure.bbox.height / 2.0
+}
+
+/// Appends an image reference as a paragraph of its own.
+fn push_figure(markdown: &mut String, figure: &Figure) {
+    if !markdown.is_empty() && !markdown.ends_with("\n\n") {
+        markdown.push_str(if markdown.ends_with('\n') {
+            "\n"
+        } else {
+            "\n\n"
+        });
+    }
+    markdown.push_str(&format!("![]({})\n\n", figure.path));
+}
+
+/// Whether a caption line opens a new caption rather than continuing the previous one.
+fn starts_caption(line: &LineItem) -> bool {
+    caption_label(&join_line_items(line)).is_some()
+}
+
+fn markdown_emphasis(item: &TextItem) -> String {
+    let text = match item.format {
+        Some(WordFormat::Bold) => wrap_trimmed(&item.text, "**", "**"),
+        Some(WordFormat::Italic) => wrap_trimmed(&item.text, "_", "_"),
+        Some(WordFormat::BoldItalic) => wrap_trimmed(&item.text, "**_", "_**"),
+        _ => item.text.clone(),
+    };
+    if item.highlighted {
+        wrap_trimmed(&text, "==", "==")
+    } else {
+        text
+    }
+}
+
+/// Records the page-relative (0-based) line range of a rendered line. Lines that continue
+/// the previous block without a blank line in between, or on the same line, are folded
+/// into its span.
+fn record_span(
+    spans: &mut Vec<SourceSpan>,
+    line: &LineItem,
+    page: u16,
+    start_line: usize,
+    end_line: usize,
+) {
+    let bbox = BoundingBox::of_line(line);
+
+    if let Some(last) = spans.last_mut() {
+        if last.block_type == line.block_type && start_line <= last.end_line + 1 {
+            last.end_line = end_line;
+            last.bbox = last.bbox.union(&bbox);
+            last.lines.push(bbox);
+            return;
+        }
+    }
+
+    spans.push(SourceSpan {
+        start_line,
+        end_line,
+        page,
+        block_type: line.block_type,
+        bbox,
+        lines: vec![bbox],
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{GlobalStats, Page};
+
+    fn figure(path: &str, y: f64) -> Figure {
+        Figure {
+            bbox: BoundingBox {
+                x: 50.0,
+                y,
+                width: 200.0,
+                height: 100.0,
+            },
+            path: path.to_string(),
+            data: Vec::new(),
+        }
+    }
+
+    fn line(text: &str, y: f64, cells: &[&str]) -> ItemType {
+        ItemType::LineItem(LineItem {
+            items: vec![TextItem {
+                text: text.to_string(),
+                y,
+                font_size: 10.0,
+                ..Default::default()
+            }],
+            y,
+            block_type: if cells.is_empty() {
+                BlockType::Paragraph
+            } else {
+                BlockType::TableRow
+            },
+            cells: cells.iter().map(|c| c.to_string()).collect(),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn renders_table_rows_as_a_pipe_table() {
+        let mut result = ParseResult {
+            pages: vec![Page {
+                items: vec![
+                    line("Limits:", 700.0, &[]),
+                    line("Name Size", 688.0, &["Name", "Size"]),
+                    line("Width 12|14", 676.0, &["Width", "12|14"]),
+                    line("Depth", 664.0, &["Depth", ""]),
+                    line("Weight is not limited.", 652.0, &[]),
+                ],
+                ..Default::default()
+            }],
+            globals: GlobalStats {
+                most_used_distance: 12.0,
+                ..Default::default()
+            },
+        };
+        ToMarkdown { verbose: false }.transform(&mut result);
+        let ItemType::Markdown(markdown) = &result.pages[0].items[0] else {
+            panic!("no markdown");
+        };
+        assert_eq!(
+            markdown,
+            "Limits:\n\n| Name | Size |\n| --- | --- |\n| Width | 12\\|14 |\n| Depth |  |\n\n\
+             Weight is not limited.\n"
+        );
+
+        // The table's rows, rule included, fold into one span between its blank lines
+        let ItemType::SourceMap(spans) = &result.pages[0].items[1] else {
+            panic!("no source map");
+        };
+        let ranges: Vec<_> = spans
+            .iter()
+            .map(|span| (span.block_type, span.start_line, span.end_line))
+            .collect();
+        assert_eq!(
+            ranges,
+            [
+                (BlockType::Paragraph, 0, 0),
+                (BlockType::TableRow, 2, 5),
+                (BlockType::Paragraph, 7, 7),
+            ]
+        );
+    }
+
+    #[test]
+    fn places_figures_between_the_lines_around_them() {
+        let mut result = ParseResult {
+            pages: vec![Page {
+                items: vec![
+                    line("See the chart below.", 700.0, &[]),
+                    line("Sales doubled.", 560.0, &[]),
+                ],
+                // Listed out of order; the one below all text closes the page
+                figures: vec![
+                    figure("assets/page-1-1.jpg", 300.0),
+                    figure("assets/page-1-2.jpg", 680.0),
+                ],
+                ..Default::default()
+            }],
+            globals: GlobalStats {
+                most_used_distance: 12.0,
+                ..Default::default()
+            },
+        };
+        ToMarkdown { verbose: false }.transform(&mut result);
+        let ItemType::Markdown(markdown) = &result.pages[0].items[0] else {
+            panic!("no markdown");
+        };
+        assert_eq!(
+            markdown,
+            "See the chart below.\n\n![](assets/page-1-2.jpg)\n\nSales doubled.\n\n\
+             ![](assets/page-1-1.jpg)\n\n"
+        );
+    }
+}
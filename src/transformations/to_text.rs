@@ -0,0 +1,196 @@
+use crate::models::{BlockType, ItemType, ParseResult};
+use crate::transformations::common::{
+    caption_label, cjk_boundary, clean_line_text, ends_sentence, join_line_items,
+    scanned_placeholder, Transformation, LOW_CONFIDENCE_NOTE,
+};
+
+/// Renders plain text for search indexing: no markup, one reflowed line per paragraph,
+/// blocks separated by blank lines.
+pub struct ToText {
+    pub verbose: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum OpenBlock {
+    None,
+    Prose,
+    Code,
+    Entries,
+    Caption,
+    /// Table rows with this many columns
+    Table(usize),
+    /// A page placeholder or the low-confidence note: never continued
+    Note,
+}
+
+impl Transformation for ToText {
+    fn transform(&self, result: &mut ParseResult) {
+        let paragraph_gap = result.globals.most_used_distance * 1.1;
+        let total = result.pages.len();
+
+        let mut open = OpenBlock::None;
+        let mut last_text = String::new();
+        let mut last_hyphenated = false;
+        let mut last_y: Option<f64> = None;
+
+        for (counter, page) in result.pages.iter_mut().enumerate() {
+            if self.verbose && ((counter + 1) % 50 == 0 || counter + 1 == total) {
+                crate::lgger!("ToText: Processed {}/{} pages...", counter + 1, total);
+            }
+
+            let mut text = String::new();
+            let new_page = last_y.is_some();
+            last_y = None;
+
+            let has_lines = page
+                .items
+                .iter()
+                .any(|item| matches!(item, ItemType::LineItem(_)));
+            if page.low_confidence && has_lines {
+                push_note(&mut text, &mut open, LOW_CONFIDENCE_NOTE);
+            }
+            if page.scanned && !has_lines {
+                push_note(&mut text, &mut open, &scanned_placeholder(page.index));
+            }
+
+            for item in &page.items {
+                let ItemType::LineItem(line) = item else {
+                    continue;
+                };
+
+                let continues = match last_y {
+                    Some(y) => (y - line.y).abs() <= paragraph_gap,
+                    None => new_page && !ends_sentence(&last_text),
+                };
+                last_y = Some(line.y);
+
+                let merged = join_line_items(line);
+                let hyphenated = merged.trim_end().ends_with('-');
+
+                let (kind, content) = match line.block_type {
+                    BlockType::Code => (OpenBlock::Code, merged),
+                    BlockType::TocItem(level) => (
+                        OpenBlock::Entries,
+                        format!(
+                            "{}{}",
+                            "  ".repeat(level),
+                            merged.split_whitespace().collect::<Vec<_>>().join(" ")
+                        ),
+                    ),
+                    BlockType::ListItem => (OpenBlock::Entries, clean_line_text(merged)),
+                    BlockType::Caption => (OpenBlock::Caption, clean_line_text(merged)),
+                    // One line per row, cells separated by tabs
+                    BlockType::TableRow => {
+                        (OpenBlock::Table(line.cells.len()), line.cells.join("\t"))
+                    }
+                    _ => (OpenBlock::Prose, clean_line_text(merged)),
+                };
+                let is_header = line.block_type.heading_level().is_some();
+                let reflowed = matches!(kind, OpenBlock::Prose | OpenBlock::Caption);
+                let joins = match kind {
+                    OpenBlock::Prose => continues,
+                    // Each labelled caption line starts a caption of its own
+                    OpenBlock::Caption => caption_label(&content).is_none(),
+                    _ => true,
+                };
+
+                if !is_header && open == kind && joins {
+                    // Prose is reflowed onto one line; other blocks keep their line breaks
+                    if !reflowed {
+                        text.push('\n');
+                    } else if !last_hyphenated && !cjk_boundary(&last_text, &content) {
+                        text.push(' ');
+                    }
+                } else if open != OpenBlock::None {
+                    text.push_str("\n\n");
+                }
+                text.push_str(&content);
+
+                // Headings stand alone: the next line always starts a new block
+                open = if is_header { OpenBlock::None } else { kind };
+                if is_header {
+                    text.push_str("\n\n");
+                }
+                last_text = content;
+                last_hyphenated = hyphenated && reflowed;
+            }
+
+            page.items = vec![ItemType::PlainText(text)];
+        }
+    }
+}
+
+/// Writes `note` in brackets as a block of its own.
+fn push_note(text: &mut String, open: &mut OpenBlock, note: &str) {
+    if *open != OpenBlock::None {
+        text.push_str("\n\n");
+    }
+    text.push_str(&format!("[{}]", note));
+    *open = OpenBlock::Note;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{GlobalStats, LineItem, Page, TextItem};
+
+    fn line(text: &str, y: f64) -> ItemType {
+        ItemType::LineItem(LineItem {
+            items: vec![TextItem {
+                text: text.to_string(),
+                y,
+                font_size: 10.0,
+                ..Default::default()
+            }],
+            y,
+            ..Default::default()
+        })
+    }
+
+    fn render(pages: Vec<Page>) -> String {
+        let mut result = ParseResult {
+            pages,
+            globals: GlobalStats {
+                most_used_distance: 12.0,
+                ..Default::default()
+            },
+        };
+        ToText { verbose: false }.transform(&mut result);
+        result
+            .pages
+            .iter()
+            .flat_map(|page| &page.items)
+            .filter_map(|item| match item {
+                ItemType::PlainText(text) => Some(text.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn marks_scanned_and_low_confidence_pages() {
+        let text = render(vec![
+            Page {
+                index: 0,
+                items: vec![line("First page.", 700.0)],
+                ..Default::default()
+            },
+            Page {
+                index: 1,
+                scanned: true,
+                ..Default::default()
+            },
+            Page {
+                index: 2,
+                low_confidence: true,
+                items: vec![line("Recognized text.", 700.0)],
+                ..Default::default()
+            },
+        ]);
+        assert_eq!(
+            text,
+            "First page.\n\n[Page 2: scanned image without a text layer]\n\n\
+             [low-confidence OCR text]\n\nRecognized text."
+        );
+    }
+}